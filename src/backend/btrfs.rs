@@ -0,0 +1,82 @@
+use super::{ioctl, SnapshotBackend};
+use crate::Error;
+use std::ffi::CString;
+use std::fs::{self, File};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+/// Inode number of the root directory of every btrfs subvolume.
+const BTRFS_FIRST_FREE_OBJECTID: u64 = 256;
+
+/// Backend storing each snapshot as a btrfs subvolume.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BtrfsBackend;
+
+impl BtrfsBackend {
+    fn is_subvolume(path: &Path) -> Result<bool, Error> {
+        Ok(fs::metadata(path)?.ino() == BTRFS_FIRST_FREE_OBJECTID)
+    }
+
+    /// Open the parent directory of `path` and return it along with the final path component.
+    fn open_parent(path: &Path) -> Result<(File, CString), Error> {
+        let parent = path.parent().ok_or(Error::Oops)?;
+        let name = path.file_name().ok_or(Error::Oops)?;
+        let name = CString::new(name.as_bytes()).map_err(|_| Error::Oops)?;
+        Ok((File::open(parent)?, name))
+    }
+}
+
+impl SnapshotBackend for BtrfsBackend {
+    fn create(&self, path: &Path) -> Result<(), Error> {
+        let (parent, name) = Self::open_parent(path)?;
+        ioctl::create_subvolume(&parent, &name)?;
+        Ok(())
+    }
+
+    fn snapshot(&self, source: &Path, dest: &Path, read_only: bool) -> Result<(), Error> {
+        let source = File::open(source)?;
+        let (parent, name) = Self::open_parent(dest)?;
+        ioctl::create_snapshot(&source, &parent, &name, read_only)?;
+        Ok(())
+    }
+
+    /// Delete the subvolume at `path`.
+    ///
+    /// The subvolume deletion ioctl drops the whole subvolume at once, rather than unlinking every
+    /// value file one at a time. Unprivileged processes may only use it if the filesystem is
+    /// mounted with `user_subvol_rm_allowed`, so fall back to a recursive unlink if permission is
+    /// denied.
+    fn delete(&self, path: &Path) -> Result<(), Error> {
+        if !Self::is_subvolume(path)? {
+            fs::remove_dir_all(path)?;
+            return Ok(());
+        }
+
+        // Unprivileged users can't delete read-only subvolumes, and files in a read-only
+        // subvolume can't be unlinked, so make it writable first.
+        let subvolume = File::open(path)?;
+        if ioctl::subvolume_info(&subvolume)?.read_only {
+            ioctl::set_read_only(&subvolume, false)?;
+        }
+        drop(subvolume);
+
+        let (parent, name) = Self::open_parent(path)?;
+        match ioctl::delete_subvolume(&parent, &name) {
+            Ok(()) => Ok(()),
+            Err(e) if matches!(e.raw_os_error(), Some(libc::EPERM) | Some(libc::EACCES)) => {
+                fs::remove_dir_all(path)?;
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn open(&self, path: &Path) -> Result<(), Error> {
+        if Self::is_subvolume(path)? {
+            Ok(())
+        } else {
+            Err(Error::NotASnapshot(path.to_path_buf()))
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Safe wrappers for the btrfs subvolume ioctls.
+//!
+//! These operate directly on file descriptors and never resolve subvolumes to paths, so unlike
+//! `libbtrfsutil` they don't require CAP_SYS_ADMIN. Deleting a subvolume still requires either
+//! CAP_SYS_ADMIN or the `user_subvol_rm_allowed` mount option.
+use std::ffi::CStr;
+use std::fs::File;
+use std::io;
+use std::os::fd::AsRawFd;
+
+const BTRFS_IOCTL_MAGIC: u32 = 0x94;
+const BTRFS_PATH_NAME_MAX: usize = 4087;
+const BTRFS_SUBVOL_NAME_MAX: usize = 4039;
+const BTRFS_VOL_NAME_MAX: usize = 255;
+const BTRFS_UUID_SIZE: usize = 16;
+
+/// Subvolume flag indicating that it is read-only.
+const BTRFS_SUBVOL_RDONLY: u64 = 1 << 1;
+
+/// Argument for `BTRFS_IOC_SUBVOL_CREATE` and `BTRFS_IOC_SNAP_DESTROY`.
+#[repr(C)]
+struct VolArgs {
+    fd: i64,
+    name: [u8; BTRFS_PATH_NAME_MAX + 1],
+}
+
+/// Argument for `BTRFS_IOC_SNAP_CREATE_V2`.
+#[repr(C)]
+struct VolArgsV2 {
+    fd: i64,
+    transid: u64,
+    flags: u64,
+    unused: [u64; 4],
+    name: [u8; BTRFS_SUBVOL_NAME_MAX + 1],
+}
+
+#[repr(C)]
+struct Timespec {
+    sec: u64,
+    nsec: u32,
+}
+
+/// Argument for `BTRFS_IOC_GET_SUBVOL_INFO`.
+#[repr(C)]
+struct GetSubvolInfoArgs {
+    treeid: u64,
+    name: [u8; BTRFS_VOL_NAME_MAX + 1],
+    parent_id: u64,
+    dirid: u64,
+    generation: u64,
+    flags: u64,
+    uuid: [u8; BTRFS_UUID_SIZE],
+    parent_uuid: [u8; BTRFS_UUID_SIZE],
+    received_uuid: [u8; BTRFS_UUID_SIZE],
+    ctransid: u64,
+    otransid: u64,
+    stransid: u64,
+    rtransid: u64,
+    ctime: Timespec,
+    otime: Timespec,
+    stime: Timespec,
+    rtime: Timespec,
+    reserved: [u64; 8],
+}
+
+// Check the argument layouts against the sizes of the kernel's structs.
+const _: () = assert!(std::mem::size_of::<VolArgs>() == 4096);
+const _: () = assert!(std::mem::size_of::<VolArgsV2>() == 4096);
+const _: () = assert!(std::mem::size_of::<GetSubvolInfoArgs>() == 504);
+
+const BTRFS_IOC_SUBVOL_CREATE: libc::Ioctl = libc::_IOW::<VolArgs>(BTRFS_IOCTL_MAGIC, 14);
+const BTRFS_IOC_SNAP_DESTROY: libc::Ioctl = libc::_IOW::<VolArgs>(BTRFS_IOCTL_MAGIC, 15);
+const BTRFS_IOC_SNAP_CREATE_V2: libc::Ioctl = libc::_IOW::<VolArgsV2>(BTRFS_IOCTL_MAGIC, 23);
+const BTRFS_IOC_SUBVOL_SETFLAGS: libc::Ioctl = libc::_IOW::<u64>(BTRFS_IOCTL_MAGIC, 26);
+const BTRFS_IOC_GET_SUBVOL_INFO: libc::Ioctl =
+    libc::_IOR::<GetSubvolInfoArgs>(BTRFS_IOCTL_MAGIC, 60);
+
+/// Information about a subvolume.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubvolumeInfo {
+    /// ID of the subvolume's tree.
+    pub id: u64,
+    /// ID of the subvolume containing this one.
+    pub parent_id: u64,
+    /// Transaction ID of the last change to the subvolume.
+    pub generation: u64,
+    pub read_only: bool,
+}
+
+/// Create an empty subvolume called `name` in the directory `parent`.
+pub fn create_subvolume(parent: &File, name: &CStr) -> io::Result<()> {
+    let mut args: VolArgs = unsafe { std::mem::zeroed() };
+    copy_name(&mut args.name, name)?;
+    unsafe { ioctl(parent, BTRFS_IOC_SUBVOL_CREATE, &mut args) }
+}
+
+/// Snapshot the subvolume `source`, creating a subvolume called `name` in the directory `parent`.
+pub fn create_snapshot(
+    source: &File,
+    parent: &File,
+    name: &CStr,
+    read_only: bool,
+) -> io::Result<()> {
+    let mut args: VolArgsV2 = unsafe { std::mem::zeroed() };
+    args.fd = source.as_raw_fd().into();
+    if read_only {
+        args.flags = BTRFS_SUBVOL_RDONLY;
+    }
+    copy_name(&mut args.name, name)?;
+    unsafe { ioctl(parent, BTRFS_IOC_SNAP_CREATE_V2, &mut args) }
+}
+
+/// Delete the subvolume called `name` in the directory `parent`.
+pub fn delete_subvolume(parent: &File, name: &CStr) -> io::Result<()> {
+    let mut args: VolArgs = unsafe { std::mem::zeroed() };
+    copy_name(&mut args.name, name)?;
+    unsafe { ioctl(parent, BTRFS_IOC_SNAP_DESTROY, &mut args) }
+}
+
+/// Make the subvolume `subvolume` read-only or writable.
+pub fn set_read_only(subvolume: &File, read_only: bool) -> io::Result<()> {
+    let mut flags = if read_only { BTRFS_SUBVOL_RDONLY } else { 0 };
+    unsafe { ioctl(subvolume, BTRFS_IOC_SUBVOL_SETFLAGS, &mut flags) }
+}
+
+/// Get information about the subvolume containing `file`.
+pub fn subvolume_info(file: &File) -> io::Result<SubvolumeInfo> {
+    let mut args: GetSubvolInfoArgs = unsafe { std::mem::zeroed() };
+    unsafe { ioctl(file, BTRFS_IOC_GET_SUBVOL_INFO, &mut args)? };
+    Ok(SubvolumeInfo {
+        id: args.treeid,
+        parent_id: args.parent_id,
+        generation: args.generation,
+        read_only: args.flags & BTRFS_SUBVOL_RDONLY != 0,
+    })
+}
+
+/// Copy `name` into a fixed-size, null-terminated buffer.
+fn copy_name(buf: &mut [u8], name: &CStr) -> io::Result<()> {
+    let bytes = name.to_bytes_with_nul();
+    if bytes.len() > buf.len() {
+        return Err(io::Error::from_raw_os_error(libc::ENAMETOOLONG));
+    }
+    buf[..bytes.len()].copy_from_slice(bytes);
+    Ok(())
+}
+
+/// Issue `request` on `file`.
+///
+/// # Safety
+///
+/// `T` must be the argument type expected by `request`.
+unsafe fn ioctl<T>(file: &File, request: libc::Ioctl, arg: &mut T) -> io::Result<()> {
+    if libc::ioctl(file.as_raw_fd(), request, arg as *mut T) < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
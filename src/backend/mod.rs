@@ -0,0 +1,113 @@
+//! Snapshot backends.
+//!
+//! A backend knows how to create, snapshot and delete the directories that hold each generation
+//! and checkpoint. The btrfs backend uses subvolumes, which makes snapshots constant time. The
+//! portable backend works on any filesystem by copying, using reflinks where supported.
+use crate::Error;
+use std::ffi::CString;
+use std::fmt::Debug;
+use std::fs;
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+pub mod btrfs;
+pub mod ioctl;
+pub mod portable;
+
+pub use btrfs::BtrfsBackend;
+pub use portable::PortableBackend;
+
+const BACKEND_FILENAME: &str = "backend";
+
+pub trait SnapshotBackend: Debug + Send + Sync {
+    /// Create a new, empty snapshot at `path`.
+    fn create(&self, path: &Path) -> Result<(), Error>;
+
+    /// Create a copy of the snapshot at `source` at `dest`.
+    ///
+    /// This must fail without modifying `dest` if it already exists.
+    fn snapshot(&self, source: &Path, dest: &Path, read_only: bool) -> Result<(), Error>;
+
+    /// Delete the snapshot at `path`, which may be read-only.
+    ///
+    /// This should also succeed for a plain directory, such as one left behind by an interrupted
+    /// call to `snapshot`.
+    fn delete(&self, path: &Path) -> Result<(), Error>;
+
+    /// Check that `path` is an existing snapshot.
+    fn open(&self, path: &Path) -> Result<(), Error>;
+}
+
+/// The backends built into the crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendKind {
+    Btrfs,
+    Portable,
+}
+
+impl BackendKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Btrfs => "btrfs",
+            Self::Portable => "portable",
+        }
+    }
+
+    pub fn backend(self) -> Arc<dyn SnapshotBackend> {
+        match self {
+            Self::Btrfs => Arc::new(BtrfsBackend),
+            Self::Portable => Arc::new(PortableBackend),
+        }
+    }
+
+    /// Choose the btrfs backend if `path` is on a btrfs filesystem, and the portable one otherwise.
+    pub fn detect(path: &Path) -> Result<Self, Error> {
+        let path = CString::new(path.as_os_str().as_bytes()).map_err(|_| Error::Oops)?;
+        let mut stat = MaybeUninit::<libc::statfs>::uninit();
+        let res = unsafe { libc::statfs(path.as_ptr(), stat.as_mut_ptr()) };
+        if res != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let stat = unsafe { stat.assume_init() };
+
+        if stat.f_type == libc::BTRFS_SUPER_MAGIC as _ {
+            Ok(Self::Btrfs)
+        } else {
+            Ok(Self::Portable)
+        }
+    }
+
+    /// Read the backend recorded in the database root.
+    ///
+    /// Databases created before backends were configurable don't have a record and use btrfs.
+    pub fn read(root_path: &Path) -> Result<Self, Error> {
+        let contents = match fs::read_to_string(root_path.join(BACKEND_FILENAME)) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::Btrfs),
+            Err(e) => return Err(e.into()),
+        };
+        contents.trim().parse()
+    }
+
+    /// Record this backend in the database root, so that it is used when the database is opened.
+    pub fn write(self, root_path: &Path) -> Result<(), Error> {
+        fs::write(root_path.join(BACKEND_FILENAME), self.as_str())?;
+        Ok(())
+    }
+}
+
+impl FromStr for BackendKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "btrfs" => Ok(Self::Btrfs),
+            "portable" => Ok(Self::Portable),
+            _ => Err(Error::UnknownBackend(s.to_string())),
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use super::SnapshotBackend;
+use crate::Error;
+use std::ffi::CString;
+use std::fs::{self, File};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+/// Backend storing each snapshot as a plain directory, for filesystems without subvolumes.
+///
+/// Snapshots are made by copying every file. Where the filesystem supports reflinks (e.g. XFS)
+/// the copy shares data blocks with the source, otherwise the data is copied in full.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PortableBackend;
+
+impl PortableBackend {
+    /// Recursively copy the directory `source` to `dest`, which must not exist.
+    fn copy_dir(source: &Path, dest: &Path) -> Result<(), Error> {
+        fs::create_dir(dest)?;
+        for entry in fs::read_dir(source)? {
+            let entry = entry?;
+            let source_path = entry.path();
+            let dest_path = dest.join(entry.file_name());
+            if entry.file_type()?.is_dir() {
+                Self::copy_dir(&source_path, &dest_path)?;
+            } else {
+                Self::copy_file(&source_path, &dest_path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy a single file, sharing its data blocks with a reflink if possible.
+    fn copy_file(source: &Path, dest: &Path) -> Result<(), Error> {
+        let source_file = File::open(source)?;
+        let dest_file = File::create(dest)?;
+        let res = unsafe {
+            libc::ioctl(
+                dest_file.as_raw_fd(),
+                libc::FICLONE,
+                source_file.as_raw_fd(),
+            )
+        };
+        if res == 0 {
+            return Ok(());
+        }
+
+        let e = io::Error::last_os_error();
+        match e.raw_os_error() {
+            // Reflinks aren't supported by this filesystem, or not between these two files.
+            Some(libc::EOPNOTSUPP)
+            | Some(libc::ENOTTY)
+            | Some(libc::EXDEV)
+            | Some(libc::EINVAL) => {
+                drop(dest_file);
+                fs::copy(source, dest)?;
+                Ok(())
+            }
+            _ => Err(e.into()),
+        }
+    }
+
+    /// Rename `source` to `dest`, failing with `AlreadyExists` rather than replacing `dest`.
+    fn rename_no_replace(source: &Path, dest: &Path) -> Result<(), Error> {
+        let source = CString::new(source.as_os_str().as_bytes()).map_err(|_| Error::Oops)?;
+        let dest = CString::new(dest.as_os_str().as_bytes()).map_err(|_| Error::Oops)?;
+        let res = unsafe {
+            libc::renameat2(
+                libc::AT_FDCWD,
+                source.as_ptr(),
+                libc::AT_FDCWD,
+                dest.as_ptr(),
+                libc::RENAME_NOREPLACE,
+            )
+        };
+        if res != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(())
+    }
+
+    /// Recursively set the permissions of every file and directory under `path`.
+    fn set_read_only(path: &Path, read_only: bool) -> Result<(), Error> {
+        let metadata = fs::symlink_metadata(path)?;
+        if metadata.is_dir() {
+            // Directories must be writable before their children can be modified, and made
+            // read-only only after.
+            if !read_only {
+                Self::set_permissions(path, metadata.permissions(), false)?;
+            }
+            for entry in fs::read_dir(path)? {
+                Self::set_read_only(&entry?.path(), read_only)?;
+            }
+            if read_only {
+                Self::set_permissions(path, metadata.permissions(), true)?;
+            }
+        } else {
+            Self::set_permissions(path, metadata.permissions(), read_only)?;
+        }
+        Ok(())
+    }
+
+    fn set_permissions(
+        path: &Path,
+        mut permissions: fs::Permissions,
+        read_only: bool,
+    ) -> Result<(), Error> {
+        if permissions.readonly() != read_only {
+            #[allow(clippy::permissions_set_readonly_false)]
+            permissions.set_readonly(read_only);
+            fs::set_permissions(path, permissions)?;
+        }
+        Ok(())
+    }
+}
+
+impl SnapshotBackend for PortableBackend {
+    fn create(&self, path: &Path) -> Result<(), Error> {
+        fs::create_dir(path)?;
+        Ok(())
+    }
+
+    fn snapshot(&self, source: &Path, dest: &Path, read_only: bool) -> Result<(), Error> {
+        // Copy to a temporary path and rename into place, so that an interrupted copy is never
+        // mistaken for a complete snapshot.
+        let name = dest.file_name().ok_or(Error::Oops)?;
+        let mut tmp_name = std::ffi::OsString::from(".");
+        tmp_name.push(name);
+        tmp_name.push(".tmp");
+        let tmp_path = dest.with_file_name(tmp_name);
+
+        if tmp_path.exists() {
+            self.delete(&tmp_path)?;
+        }
+        Self::copy_dir(source, &tmp_path)?;
+        // Copies may keep the permissions of their source, which is read-only when restoring a
+        // checkpoint.
+        Self::set_read_only(&tmp_path, read_only)?;
+        if let Err(e) = Self::rename_no_replace(&tmp_path, dest) {
+            self.delete(&tmp_path)?;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn delete(&self, path: &Path) -> Result<(), Error> {
+        Self::set_read_only(path, false)?;
+        fs::remove_dir_all(path)?;
+        Ok(())
+    }
+
+    fn open(&self, path: &Path) -> Result<(), Error> {
+        if path.is_dir() {
+            Ok(())
+        } else {
+            Err(Error::NotASnapshot(path.to_path_buf()))
+        }
+    }
+}
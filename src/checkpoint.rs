@@ -0,0 +1,59 @@
+//! Named, read-only snapshots of the database.
+//!
+//! Checkpoints live under `{root}/checkpoints/{name}`. Each is a read-only snapshot of the
+//! generation that was committed when the checkpoint was taken, made by the database's backend:
+//! a read-only subvolume on btrfs, or a copied directory with read-only permissions on the
+//! portable backend, sharing data with the generation via `FICLONE` where the filesystem allows.
+use crate::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const CHECKPOINTS_DIR: &str = "checkpoints";
+
+/// A checkpoint in use by read transactions or a restore, which can't be deleted until every
+/// reference to it is dropped.
+#[derive(Debug)]
+pub struct PinnedCheckpoint {
+    pub(crate) name: String,
+    pub(crate) path: PathBuf,
+}
+
+/// Path to the directory containing all checkpoints.
+pub fn checkpoints_dir(root_path: &Path) -> PathBuf {
+    root_path.join(CHECKPOINTS_DIR)
+}
+
+/// Path to the checkpoint called `name`, after checking that the name is valid.
+pub fn checkpoint_path(root_path: &Path, name: &str) -> Result<PathBuf, Error> {
+    validate_checkpoint_name(name)?;
+    Ok(checkpoints_dir(root_path).join(name))
+}
+
+/// Checkpoint names must be a single path component, and must not be hidden.
+///
+/// Hidden names are reserved for temporary files created by the snapshot backend.
+fn validate_checkpoint_name(name: &str) -> Result<(), Error> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\0']) {
+        return Err(Error::InvalidCheckpointName(name.to_string()));
+    }
+    Ok(())
+}
+
+/// List the names of all checkpoints, in sorted order.
+pub fn list_checkpoints(root_path: &Path) -> Result<Vec<String>, Error> {
+    let entries = match fs::read_dir(checkpoints_dir(root_path)) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+    let mut names = vec![];
+    for entry in entries {
+        let name = entry?.file_name().into_string().map_err(|_| Error::Oops)?;
+        if !name.starts_with('.') {
+            names.push(name);
+        }
+    }
+    names.sort();
+    Ok(names)
+}
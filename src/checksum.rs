@@ -0,0 +1,91 @@
+//! Per-value checksums, for detecting corrupt or truncated values.
+//!
+//! Checksums cover the bytes as stored on disk, after compression, and are kept in the index
+//! alongside each key.
+use crate::Error;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChecksumMode {
+    #[default]
+    None,
+    /// CRC-32C (Castagnoli) of each stored value.
+    Crc32c,
+}
+
+impl ChecksumMode {
+    /// Checksum of `bytes`, or `None` if checksums are disabled.
+    pub fn checksum(self, bytes: &[u8]) -> Option<u32> {
+        match self {
+            Self::None => None,
+            Self::Crc32c => Some(crc32c(bytes)),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Crc32c => "crc32c",
+        }
+    }
+}
+
+impl FromStr for ChecksumMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "none" => Ok(Self::None),
+            "crc32c" => Ok(Self::Crc32c),
+            _ => Err(Error::InvalidTableOptions(s.to_string())),
+        }
+    }
+}
+
+/// Lookup table for CRC-32C, using the reflected Castagnoli polynomial.
+const CRC32C_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0x82f63b78
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// CRC-32C of `bytes`. Checksums are stored on disk, so this must never change.
+fn crc32c(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, &byte| {
+        (crc >> 8) ^ CRC32C_TABLE[((crc ^ byte as u32) & 0xff) as usize]
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn crc32c_known_values() {
+        assert_eq!(crc32c(b""), 0);
+        assert_eq!(crc32c(b"123456789"), 0xe3069283);
+        assert_eq!(crc32c(&[0; 32]), 0x8a9136aa);
+    }
+
+    #[test]
+    fn checksum_names() {
+        for mode in [ChecksumMode::None, ChecksumMode::Crc32c] {
+            assert_eq!(mode.as_str().parse::<ChecksumMode>().unwrap(), mode);
+        }
+        assert!("xxhash".parse::<ChecksumMode>().is_err());
+    }
+}
@@ -0,0 +1,84 @@
+//! Custom key orderings, installed as SQLite collations on a table's index.
+use crate::Error;
+use std::cmp::Ordering;
+use std::ffi::CString;
+use std::fmt;
+use std::os::raw::{c_int, c_void};
+use std::slice;
+
+/// A named ordering on keys.
+///
+/// The name is stored with the table, and the same comparator must be supplied every time the
+/// table is opened. The comparator must be a total order and must not panic. It may return
+/// `Equal` for keys that differ, as a case-insensitive ordering would; such keys are still
+/// distinct, and are ordered by their bytes.
+#[derive(Clone, Copy)]
+pub struct Comparator {
+    pub name: &'static str,
+    pub compare: fn(&[u8], &[u8]) -> Ordering,
+}
+
+impl fmt::Debug for Comparator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Comparator")
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+impl Comparator {
+    /// Check that the name can be used as an SQLite collation and in the options file.
+    pub fn validate_name(name: &str) -> Result<(), Error> {
+        if name.is_empty() || !name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_') {
+            return Err(Error::InvalidComparatorName(name.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Register this comparator as a collation on the SQLite connection `db`.
+    pub(crate) fn register(&self, db: *mut sqlite3_sys::sqlite3) -> Result<(), Error> {
+        Self::validate_name(self.name)?;
+        let name = CString::new(self.name).map_err(|_| Error::Oops)?;
+        let res = unsafe {
+            sqlite3_sys::sqlite3_create_collation_v2(
+                db,
+                name.as_ptr(),
+                sqlite3_sys::SQLITE_UTF8,
+                self.compare as *mut c_void,
+                Some(compare_callback),
+                None,
+            )
+        };
+        if res != sqlite3_sys::SQLITE_OK {
+            return Err(Error::Sqlite(sqlite::Error {
+                code: Some(res as isize),
+                message: Some(format!("failed to register collation {}", self.name)),
+            }));
+        }
+        Ok(())
+    }
+}
+
+/// Collation callback passed to SQLite, with the comparator function as its argument.
+extern "C" fn compare_callback(
+    arg: *mut c_void,
+    len_a: c_int,
+    a: *const c_void,
+    len_b: c_int,
+    b: *const c_void,
+) -> c_int {
+    unsafe {
+        let compare: fn(&[u8], &[u8]) -> Ordering = std::mem::transmute(arg);
+        let a = bytes_from_raw(a, len_a);
+        let b = bytes_from_raw(b, len_b);
+        compare(a, b).then_with(|| a.cmp(b)) as c_int
+    }
+}
+
+unsafe fn bytes_from_raw<'a>(ptr: *const c_void, len: c_int) -> &'a [u8] {
+    if ptr.is_null() || len <= 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(ptr as *const u8, len as usize)
+    }
+}
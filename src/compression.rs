@@ -0,0 +1,240 @@
+//! Value compression.
+//!
+//! Compression is either delegated to btrfs, by setting the compression property on the table
+//! directory, or done in-process. Values compressed in-process start with a one byte header
+//! naming the codec, so a table can contain values stored with different codecs, including values
+//! stored raw because compression didn't make them smaller.
+use crate::Error;
+use std::ffi::CString;
+use std::fmt;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::str::FromStr;
+
+const HEADER_RAW: u8 = 0;
+const HEADER_ZSTD: u8 = 1;
+const HEADER_LZ4: u8 = 2;
+
+const BTRFS_COMPRESSION_PROPERTY: &str = "btrfs.compression";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    #[default]
+    None,
+    /// Have btrfs compress value files with the given algorithm.
+    Btrfs(BtrfsCompression),
+    /// Compress values with zstd at `level`. Requires the `zstd` feature.
+    Zstd { level: i32 },
+    /// Compress values with LZ4. Requires the `lz4` feature.
+    Lz4,
+}
+
+/// Compression algorithms supported by btrfs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BtrfsCompression {
+    Zlib,
+    Lzo,
+    Zstd,
+}
+
+impl Compression {
+    /// Check whether values are compressed in-process, and so have a header.
+    pub fn is_in_process(self) -> bool {
+        matches!(self, Self::Zstd { .. } | Self::Lz4)
+    }
+
+    /// Check that support for this compression was compiled in.
+    pub fn check_supported(self) -> Result<(), Error> {
+        match self {
+            Self::Zstd { .. } if !cfg!(feature = "zstd") => {
+                Err(Error::UnsupportedCompression(self.to_string()))
+            }
+            Self::Lz4 if !cfg!(feature = "lz4") => {
+                Err(Error::UnsupportedCompression(self.to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Encode `value` for storage.
+    ///
+    /// Values that don't shrink are stored raw, behind a header if compression is in-process.
+    pub fn compress(self, value: &[u8]) -> Result<Vec<u8>, Error> {
+        let (header, compressed) = match self {
+            Self::None | Self::Btrfs(_) => return Ok(value.to_vec()),
+            Self::Zstd { level } => (HEADER_ZSTD, zstd_compress(value, level)?),
+            Self::Lz4 => (HEADER_LZ4, lz4_compress(value)?),
+        };
+        let mut bytes = Vec::with_capacity(1 + compressed.len().min(value.len()));
+        if compressed.len() < value.len() {
+            bytes.push(header);
+            bytes.extend_from_slice(&compressed);
+        } else {
+            bytes.push(HEADER_RAW);
+            bytes.extend_from_slice(value);
+        }
+        Ok(bytes)
+    }
+
+    /// Decode a value stored by `compress`.
+    pub fn decompress(self, bytes: &[u8]) -> Result<Vec<u8>, Error> {
+        if !self.is_in_process() {
+            return Ok(bytes.to_vec());
+        }
+        let (&header, data) = bytes
+            .split_first()
+            .ok_or_else(|| Error::Decode("missing compression header".to_string()))?;
+        match header {
+            HEADER_RAW => Ok(data.to_vec()),
+            HEADER_ZSTD => zstd_decompress(data),
+            HEADER_LZ4 => lz4_decompress(data),
+            _ => Err(Error::Decode(format!(
+                "unknown compression header {}",
+                header
+            ))),
+        }
+    }
+
+    /// Apply any filesystem-level compression to the table directory at `table_path`, so that
+    /// files created in it are compressed.
+    pub fn apply_to_dir(self, table_path: &Path) -> Result<(), Error> {
+        let Self::Btrfs(algorithm) = self else {
+            return Ok(());
+        };
+        let path = CString::new(table_path.as_os_str().as_bytes()).map_err(|_| Error::Oops)?;
+        let name = CString::new(BTRFS_COMPRESSION_PROPERTY).map_err(|_| Error::Oops)?;
+        let value = algorithm.as_str();
+        let res = unsafe {
+            libc::setxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_ptr() as *const libc::c_void,
+                value.len(),
+                0,
+            )
+        };
+        if res != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(())
+    }
+}
+
+impl BtrfsCompression {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Zlib => "zlib",
+            Self::Lzo => "lzo",
+            Self::Zstd => "zstd",
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Btrfs(algorithm) => write!(f, "btrfs:{}", algorithm.as_str()),
+            Self::Zstd { level } => write!(f, "zstd:{}", level),
+            Self::Lz4 => write!(f, "lz4"),
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidTableOptions(s.to_string());
+        match s.split_once(':') {
+            None if s == "none" => Ok(Self::None),
+            None if s == "lz4" => Ok(Self::Lz4),
+            Some(("btrfs", algorithm)) => Ok(Self::Btrfs(match algorithm {
+                "zlib" => BtrfsCompression::Zlib,
+                "lzo" => BtrfsCompression::Lzo,
+                "zstd" => BtrfsCompression::Zstd,
+                _ => return Err(invalid()),
+            })),
+            Some(("zstd", level)) => Ok(Self::Zstd {
+                level: level.parse().map_err(|_| invalid())?,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[cfg(feature = "zstd")]
+fn zstd_compress(value: &[u8], level: i32) -> Result<Vec<u8>, Error> {
+    Ok(zstd::bulk::compress(value, level)?)
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_compress(_: &[u8], level: i32) -> Result<Vec<u8>, Error> {
+    Err(Error::UnsupportedCompression(
+        Compression::Zstd { level }.to_string(),
+    ))
+}
+
+#[cfg(feature = "zstd")]
+fn zstd_decompress(data: &[u8]) -> Result<Vec<u8>, Error> {
+    Ok(zstd::stream::decode_all(data)?)
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_decompress(_: &[u8]) -> Result<Vec<u8>, Error> {
+    Err(Error::UnsupportedCompression("zstd".to_string()))
+}
+
+#[cfg(feature = "lz4")]
+fn lz4_compress(value: &[u8]) -> Result<Vec<u8>, Error> {
+    Ok(lz4_flex::compress_prepend_size(value))
+}
+
+#[cfg(not(feature = "lz4"))]
+fn lz4_compress(_: &[u8]) -> Result<Vec<u8>, Error> {
+    Err(Error::UnsupportedCompression(Compression::Lz4.to_string()))
+}
+
+#[cfg(feature = "lz4")]
+fn lz4_decompress(data: &[u8]) -> Result<Vec<u8>, Error> {
+    lz4_flex::decompress_size_prepended(data).map_err(|e| Error::Decode(e.to_string()))
+}
+
+#[cfg(not(feature = "lz4"))]
+fn lz4_decompress(_: &[u8]) -> Result<Vec<u8>, Error> {
+    Err(Error::UnsupportedCompression(Compression::Lz4.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn compression_names() {
+        for compression in [
+            Compression::None,
+            Compression::Btrfs(BtrfsCompression::Zstd),
+            Compression::Zstd { level: 3 },
+            Compression::Lz4,
+        ] {
+            assert_eq!(
+                compression.to_string().parse::<Compression>().unwrap(),
+                compression
+            );
+        }
+        assert!("btrfs:gzip".parse::<Compression>().is_err());
+    }
+
+    #[test]
+    fn incompressible_values_are_raw() {
+        let compression = Compression::Lz4;
+        if compression.check_supported().is_err() {
+            return;
+        }
+        let value = [1, 2, 3];
+        let stored = compression.compress(&value).unwrap();
+        assert_eq!(stored, [HEADER_RAW, 1, 2, 3]);
+        assert_eq!(compression.decompress(&stored).unwrap(), value);
+    }
+}
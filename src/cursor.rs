@@ -2,8 +2,6 @@ use crate::{Error, Table};
 use derivative::Derivative;
 use sqlite::CursorWithOwnership as SqliteCursor;
 use std::borrow::Cow;
-use std::fs::{self, File};
-use std::io::{self, Read};
 
 pub type OwnedKey = Vec<u8>;
 pub type OwnedValue = Vec<u8>;
@@ -22,6 +20,10 @@ pub struct Cursor<'txn> {
     current_key: Option<OwnedKey>,
     /// The value corresponding to `current_key`, or `None` if it hasn't been loaded yet.
     current_value: Option<OwnedValue>,
+    /// All values of `current_key` in a dupsort table, or `None` if they haven't been loaded yet.
+    current_dups: Option<Vec<OwnedValue>>,
+    /// Position of `current_value` within the values of `current_key`.
+    dup_index: usize,
     /// Is the cursor positioned at the first key?
     is_at_first_key: bool,
 }
@@ -31,13 +33,15 @@ impl<'txn> Cursor<'txn> {
         let rows = table
             .index_file
             .conn
-            .prepare("SELECT key FROM keys ORDER BY key ASC")?
+            .prepare("SELECT CAST(key AS BLOB) FROM keys ORDER BY key ASC")?
             .into_iter();
         Ok(Cursor {
             table,
             rows: Some(rows),
             current_key: None,
             current_value: None,
+            current_dups: None,
+            dup_index: 0,
             is_at_first_key: true,
         })
     }
@@ -59,6 +63,8 @@ impl<'txn> Cursor<'txn> {
 
         self.current_key = opt_key.clone();
         self.current_value = None;
+        self.current_dups = None;
+        self.dup_index = 0;
         self.rows = None;
 
         Ok(opt_key.map(Cow::Owned))
@@ -75,8 +81,10 @@ impl<'txn> Cursor<'txn> {
         let key = new_row.try_read::<&[u8], _>(0)?.to_vec();
 
         // Update the current key and value.
-        let prev_key = std::mem::replace(&mut self.current_key, Some(key.clone()));
+        let prev_key = self.current_key.replace(key.clone());
         self.current_value = None;
+        self.current_dups = None;
+        self.dup_index = 0;
 
         // Only if the previous key was `None` are we still at the first key.
         self.is_at_first_key = prev_key.is_none();
@@ -91,9 +99,18 @@ impl<'txn> Cursor<'txn> {
 
         if let Some(key) = &self.current_key {
             if self.current_value.is_none() {
-                let mut file = File::open(self.table.key_path(key))?;
-                let mut value = vec![];
-                file.read_to_end(&mut value)?;
+                let value = if self.table.options.dup_sort {
+                    if self.current_dups.is_none() {
+                        self.current_dups = Some(self.table.get_all(key)?);
+                    }
+                    self.current_dups
+                        .as_ref()
+                        .and_then(|dups| dups.get(self.dup_index))
+                        .cloned()
+                        .ok_or(Error::Oops)?
+                } else {
+                    self.table.get(key)?.ok_or(Error::Oops)?
+                };
                 self.current_value = Some(value);
             }
             let value = self
@@ -108,26 +125,48 @@ impl<'txn> Cursor<'txn> {
     }
 
     pub fn delete_current(&mut self) -> Result<(), Error> {
+        self.table.check_writable()?;
         let Some(key) = &self.current_key else {
             return Ok(());
         };
 
-        // Delete from the index.
-        self.table.index_file.delete_key(key)?;
-
-        // Delete from disk.
-        let key_path = self.table.key_path(key);
-        fs::remove_file(key_path).or_else(|e| {
-            if e.kind() == io::ErrorKind::NotFound {
-                Ok(())
-            } else {
-                Err(e)
-            }
-        })?;
+        // Delete from the index and disk.
+        self.table.delete(key)?;
 
         // Erase from cursor.
         self.current_value = None;
+        self.current_dups = None;
+        self.dup_index = 0;
 
         Ok(())
     }
+
+    /// Move to the first value of the current key and return it.
+    pub fn first_dup(&mut self) -> Result<Option<Value<'_>>, Error> {
+        self.dup_index = 0;
+        self.current_value = None;
+        Ok(self.get_current()?.map(|(_, value)| value))
+    }
+
+    /// Move to the next value of the current key and return it.
+    ///
+    /// Return `None` without moving if the cursor is at the key's last value. Tables without
+    /// dupsort have a single value per key.
+    pub fn next_dup(&mut self) -> Result<Option<Value<'_>>, Error> {
+        if self.get_current()?.is_none() {
+            return Ok(None);
+        }
+        let num_dups = self.current_dups.as_ref().map_or(1, Vec::len);
+        if self.dup_index + 1 >= num_dups {
+            return Ok(None);
+        }
+        self.dup_index += 1;
+        self.current_value = None;
+        Ok(self.get_current()?.map(|(_, value)| value))
+    }
+
+    /// Move to the first value of the next key, skipping the rest of the current key's values.
+    pub fn next_nodup(&mut self) -> Result<Option<Key<'_>>, Error> {
+        self.next_key()
+    }
 }
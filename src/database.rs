@@ -1,153 +1,505 @@
-use crate::{Error, Transaction};
-use btrfsutil::bindings::{btrfs_util_create_snapshot_fd2, btrfs_util_error_BTRFS_UTIL_OK};
-use btrfsutil::subvolume::Subvolume;
+use crate::backend::{BackendKind, SnapshotBackend};
+use crate::checkpoint::{self, checkpoint_path, checkpoints_dir, PinnedCheckpoint};
+use crate::lock::LockFile;
+use crate::reclaim::Reclaimer;
+use crate::recovery::{
+    migrate_legacy_generations, read_commit_marker, sync_filesystem, write_commit_marker,
+};
+use crate::verify::open_or_skip;
+use crate::{
+    DatabaseReader, Error, Inconsistency, ReadSource, ReadTransaction, RecoveryReport, Transaction,
+    VerifyReport,
+};
 use parking_lot::{Mutex, RwLock};
-use std::ffi::CString;
-use std::fs::File;
-use std::os::fd::AsRawFd;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
 use std::path::{Path, PathBuf};
-use strum::AsRefStr;
-
-#[derive(Debug, Copy, Clone, Default, AsRefStr)]
-#[strum(serialize_all = "lowercase")]
-pub enum Generation {
-    #[default]
-    Tick,
-    Tock,
-}
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+
+const GENERATION_PREFIX: &str = "gen-";
+
+/// Monotonically increasing version number of the database.
+///
+/// Each generation is stored in its own subvolume named `gen-{n}` under the database root.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Generation(pub u64);
 
 impl Generation {
     pub fn incremented(self) -> Self {
-        match self {
-            Self::Tick => Self::Tock,
-            Self::Tock => Self::Tick,
-        }
+        Self(self.0 + 1)
+    }
+
+    /// Name of the subvolume for this generation.
+    pub fn dir_name(self) -> String {
+        format!("{GENERATION_PREFIX}{}", self.0)
+    }
+
+    /// Parse a generation from a subvolume name, returning `None` if it isn't a generation.
+    pub fn from_dir_name(name: &str) -> Option<Self> {
+        name.strip_prefix(GENERATION_PREFIX)?.parse().ok()
+    }
+}
+
+impl fmt::Display for Generation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
     }
 }
 
-/// Snapshot of the database at a specific version including `Generation` and `Subvolume`.
+impl FromStr for Generation {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self)
+    }
+}
+
+/// Snapshot of the database at a specific version.
+///
+/// Snapshots are shared via `Arc` so that readers can pin the generation they started on. Once a
+/// snapshot is retired (superseded by a commit, or belonging to an aborted transaction) it is
+/// queued for deletion by the reclaimer when the last reference to it is dropped.
 #[derive(Debug)]
 pub struct Snapshot {
     pub(crate) gen: Generation,
     pub(crate) path: PathBuf,
-    // FIXME(sproul): replace this by a better wrapper
-    #[allow(dead_code)]
-    pub(crate) subvolume: Subvolume,
+    /// Whether to delete this snapshot from disk when it is dropped.
+    pub(crate) retired: AtomicBool,
+    pub(crate) reclaimer: Reclaimer,
+}
+
+impl Snapshot {
+    pub fn generation(&self) -> Generation {
+        self.gen
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub(crate) fn set_retired(&self, retired: bool) {
+        self.retired.store(retired, Ordering::SeqCst);
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        if *self.retired.get_mut() {
+            self.reclaimer.reclaim(std::mem::take(&mut self.path));
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct Database {
-    // Lock order: `write_gen` must always acquired before `read_gen`.
-    read_snapshot: RwLock<Snapshot>,
-    txn_lock: Mutex<()>,
+    // Lock order: `txn_lock` must always be acquired before `read_snapshot`.
+    /// Most recently committed snapshot. Readers clone the `Arc` to pin it.
+    read_snapshot: RwLock<Arc<Snapshot>>,
+    /// Lock held by the active write transaction, containing the next generation to allocate.
+    txn_lock: Mutex<Generation>,
+    /// Checkpoints in use, by name. The lock is held while creating or deleting a checkpoint.
+    pinned_checkpoints: Mutex<HashMap<String, Weak<PinnedCheckpoint>>>,
     root_path: PathBuf,
-    tick_path: PathBuf,
-    tock_path: PathBuf,
+    recovery_report: RecoveryReport,
+    backend: Arc<dyn SnapshotBackend>,
+    reclaimer: Reclaimer,
+    /// Exclusive lock on `root_path`, released after all other fields are dropped.
+    _lock: LockFile,
+}
+
+impl Drop for Database {
+    fn drop(&mut self) {
+        // Finish deleting obsolete snapshots. Snapshots pinned by readers that outlive the
+        // database are still deleted when they are dropped, because the reclaimer thread runs
+        // until every handle to it is gone.
+        self.reclaimer.wait();
+    }
 }
 
 impl Database {
+    /// Open the database at `root_path`, creating it if it doesn't exist.
+    ///
+    /// Existing databases are opened with the backend they were created with. If another process
+    /// has the database open, wait for it to close it.
     pub fn open_or_create(root_path: PathBuf) -> Result<Self, Error> {
-        let tick_path = Self::calc_gen_path(&root_path, Generation::Tick);
-        let tock_path = Self::calc_gen_path(&root_path, Generation::Tock);
-
-        let (gen, path) = match (tick_path.exists(), tock_path.exists()) {
-            (true, false) => (Generation::Tick, tick_path.clone()),
-            (false, true) => (Generation::Tock, tock_path.clone()),
-            (false, false) => return Self::create(root_path),
-            (true, true) => {
-                // FIXME(sproul): handle this by choosing the snapshot with the lower (?) generation
-                panic!("unclean shutdown")
+        let lock = LockFile::lock(&root_path)?;
+        Self::open_locked(root_path, lock)
+    }
+
+    /// Open the database at `root_path`, creating it if it doesn't exist.
+    ///
+    /// Fail with `Error::DatabaseLocked` if another process has the database open.
+    pub fn try_open(root_path: PathBuf) -> Result<Self, Error> {
+        let lock = LockFile::try_lock(&root_path)?;
+        Self::open_locked(root_path, lock)
+    }
+
+    /// Open a read-only handle to the database at `root_path`, which is written by another
+    /// process.
+    ///
+    /// This doesn't take the database lock. Read transactions begun on the handle follow the
+    /// writer's commits.
+    pub fn open_reader(root_path: PathBuf) -> Result<DatabaseReader, Error> {
+        DatabaseReader::open(root_path)
+    }
+
+    fn open_locked(root_path: PathBuf, lock: LockFile) -> Result<Self, Error> {
+        migrate_legacy_generations(&root_path)?;
+        let generations = Self::list_generations(&root_path)?;
+        let marker = read_commit_marker(&root_path)?;
+
+        let committed = match (marker, generations.as_slice()) {
+            (None, []) => {
+                let kind = BackendKind::detect(&root_path)?;
+                return Self::create_locked(root_path, kind, lock);
+            }
+            // A crash during `create` can leave the first generation without a marker.
+            (None, [only]) => {
+                write_commit_marker(&root_path, *only)?;
+                *only
+            }
+            (None, _) => return Err(Error::UncleanShutdown),
+            (Some(committed), _) if !generations.contains(&committed) => {
+                return Err(Error::MissingGeneration(committed));
             }
+            (Some(committed), _) => committed,
         };
 
-        let read_snapshot = RwLock::new(Snapshot {
-            gen,
-            path: path.clone(),
-            subvolume: Subvolume::create(path, None)?,
-        });
+        // Every generation other than the committed one is either the write snapshot of an
+        // interrupted transaction, or a previous generation that was awaiting deletion. Queue
+        // them for deletion, and make sure new generations don't collide with them in the
+        // meantime.
+        let backend = BackendKind::read(&root_path)?.backend();
+        let reclaimer = Reclaimer::spawn(backend.clone())?;
+        let next_gen = generations
+            .last()
+            .map_or(committed, |gen| *gen)
+            .incremented();
+        let discarded = generations
+            .into_iter()
+            .filter(|gen| *gen != committed)
+            .collect::<Vec<_>>();
+        for gen in &discarded {
+            reclaimer.reclaim(Self::calc_gen_path(&root_path, *gen));
+        }
+        let recovery_report = if discarded.is_empty() {
+            RecoveryReport::Clean
+        } else {
+            RecoveryReport::Recovered {
+                committed,
+                discarded,
+            }
+        };
+
+        let path = Self::calc_gen_path(&root_path, committed);
+        backend.open(&path)?;
+        let read_snapshot = RwLock::new(Arc::new(Snapshot {
+            gen: committed,
+            path,
+            retired: AtomicBool::new(false),
+            reclaimer: reclaimer.clone(),
+        }));
 
         Ok(Self {
             read_snapshot,
-            txn_lock: Mutex::new(()),
+            txn_lock: Mutex::new(next_gen),
+            pinned_checkpoints: Mutex::default(),
             root_path,
-            tick_path,
-            tock_path,
+            recovery_report,
+            backend,
+            reclaimer,
+            _lock: lock,
         })
     }
 
+    /// Create a new database at `root_path`.
+    ///
+    /// The btrfs backend is used if `root_path` is on btrfs, and the portable backend otherwise.
     pub fn create(root_path: PathBuf) -> Result<Self, Error> {
-        let tick_path = Self::calc_gen_path(&root_path, Generation::Tick);
-        let tock_path = Self::calc_gen_path(&root_path, Generation::Tock);
+        let kind = BackendKind::detect(&root_path)?;
+        Self::create_with_backend(root_path, kind)
+    }
 
-        let read_snapshot = RwLock::new(Snapshot {
-            gen: Generation::Tick,
-            path: tick_path.clone(),
-            subvolume: Subvolume::create(tick_path.clone(), None)?,
-        });
+    /// Create a new database at `root_path` using a specific snapshot backend.
+    pub fn create_with_backend(root_path: PathBuf, kind: BackendKind) -> Result<Self, Error> {
+        let lock = LockFile::try_lock(&root_path)?;
+        Self::create_locked(root_path, kind, lock)
+    }
+
+    fn create_locked(root_path: PathBuf, kind: BackendKind, lock: LockFile) -> Result<Self, Error> {
+        kind.write(&root_path)?;
+        let backend = kind.backend();
+        let reclaimer = Reclaimer::spawn(backend.clone())?;
+
+        let gen = Generation::default();
+        let path = Self::calc_gen_path(&root_path, gen);
+        backend.create(&path)?;
+
+        let read_snapshot = RwLock::new(Arc::new(Snapshot {
+            gen,
+            path,
+            retired: AtomicBool::new(false),
+            reclaimer: reclaimer.clone(),
+        }));
+        write_commit_marker(&root_path, gen)?;
 
         Ok(Self {
             read_snapshot,
-            txn_lock: Mutex::new(()),
+            txn_lock: Mutex::new(gen.incremented()),
+            pinned_checkpoints: Mutex::default(),
             root_path,
-            tick_path,
-            tock_path,
+            recovery_report: RecoveryReport::Clean,
+            backend,
+            reclaimer,
+            _lock: lock,
         })
     }
 
     pub fn begin_transaction(&self) -> Result<Transaction, Error> {
-        let _txn_lock = self.txn_lock.lock();
+        let mut txn_lock = self.txn_lock.lock();
 
         // Clone the read subvolume, creating a new subvolume for writing.
-        // Increment the generation.
-        let read_snapshot = self.read_snapshot.read();
-
-        let write_gen = read_snapshot.gen.incremented();
-        let write_path = self.gen_path(write_gen).clone();
-
-        // FIXME(sproul): write a better wrapper for this. The `btrfsutil` crate is unsuitable
-        // because it frequently resolves subvolumes to paths, which fails unless the CAP_SYS_ADMIN
-        // capability is held (it's also completely unnecessary).
-        let read_file = File::open(&read_snapshot.path)?;
-        let parent_file = File::open(&self.root_path)?;
-        let name = CString::new(write_gen.as_ref()).unwrap();
-        let res = unsafe {
-            btrfs_util_create_snapshot_fd2(
-                read_file.as_raw_fd(),
-                parent_file.as_raw_fd(),
-                name.as_ptr(),
-                0,
-                std::ptr::null_mut(),
-                std::ptr::null_mut(),
-            )
-        };
-        assert_eq!(res, btrfs_util_error_BTRFS_UTIL_OK);
-
-        let write_subvolume = Subvolume::get(&write_path).unwrap();
+        // Allocate a fresh generation, so the write snapshot never collides with a retired
+        // generation that is still pinned by a reader.
+        let read_snapshot = self.read_snapshot.read().clone();
 
-        let write_snapshot = Snapshot {
-            gen: write_gen,
-            path: write_path,
-            subvolume: write_subvolume,
-        };
+        let write_gen = *txn_lock;
+        *txn_lock = write_gen.incremented();
+        let write_snapshot = self.snapshot_generation(&read_snapshot.path, write_gen)?;
 
         Ok(Transaction {
-            read_snapshot: &self.read_snapshot,
-            _txn_lock,
+            db: self,
+            _txn_lock: txn_lock,
             write_snapshot,
             open_tables: vec![],
-            committed: false,
         })
     }
 
-    /// Return the filesystem path for a given generation.
-    fn gen_path(&self, generation: Generation) -> &PathBuf {
-        match generation {
-            Generation::Tick => &self.tick_path,
-            Generation::Tock => &self.tock_path,
+    /// Begin a read-only transaction on the most recently committed generation.
+    ///
+    /// This doesn't create a snapshot or wait for an in-progress write transaction. The
+    /// generation is pinned for the lifetime of the `ReadTransaction`, so later commits don't
+    /// affect it.
+    pub fn begin_read(&self) -> Result<ReadTransaction, Error> {
+        Ok(ReadTransaction {
+            source: ReadSource::Generation(self.read_snapshot.read().clone()),
+            open_tables: vec![],
+        })
+    }
+
+    /// Save a read-only copy of the most recently committed generation as checkpoint `name`.
+    pub fn checkpoint(&self, name: &str) -> Result<(), Error> {
+        let path = checkpoint_path(&self.root_path, name)?;
+        let checkpoints_dir = checkpoints_dir(&self.root_path);
+        fs::create_dir_all(&checkpoints_dir)?;
+
+        let _pinned = self.pinned_checkpoints.lock();
+        if path.exists() {
+            return Err(Error::CheckpointExists(name.to_string()));
+        }
+        // Hold a reference to the read snapshot so it can't be deleted while we copy it.
+        let read_snapshot = self.read_snapshot.read().clone();
+        // Snapshots never replace an existing directory, so if the checkpoint exists now it was
+        // created concurrently.
+        self.backend
+            .snapshot(&read_snapshot.path, &path, true)
+            .map_err(|e| {
+                if path.exists() {
+                    Error::CheckpointExists(name.to_string())
+                } else {
+                    e
+                }
+            })
+    }
+
+    /// List the names of all checkpoints, in sorted order.
+    pub fn list_checkpoints(&self) -> Result<Vec<String>, Error> {
+        checkpoint::list_checkpoints(&self.root_path)
+    }
+
+    /// Delete checkpoint `name`.
+    ///
+    /// Fails with `Error::CheckpointInUse` while a read transaction or restore is using it.
+    pub fn delete_checkpoint(&self, name: &str) -> Result<(), Error> {
+        let path = checkpoint_path(&self.root_path, name)?;
+        let pinned = self.pinned_checkpoints.lock();
+        if pinned.get(name).is_some_and(|pin| pin.strong_count() > 0) {
+            return Err(Error::CheckpointInUse(name.to_string()));
+        }
+        if !path.is_dir() {
+            return Err(Error::CheckpointNotFound(name.to_string()));
+        }
+        self.backend.delete(&path)
+    }
+
+    /// Replace the live database with the contents of checkpoint `name`.
+    ///
+    /// A writable copy of the checkpoint becomes the new read generation. This waits for any
+    /// in-progress write transaction, and has the same crash-safety guarantees as a commit.
+    pub fn restore_checkpoint(&self, name: &str) -> Result<(), Error> {
+        let checkpoint = self.pin_checkpoint(name)?;
+
+        let mut txn_lock = self.txn_lock.lock();
+        let gen = *txn_lock;
+        *txn_lock = gen.incremented();
+
+        let snapshot = self.snapshot_generation(&checkpoint.path, gen)?;
+        self.commit_snapshot(snapshot)
+    }
+
+    /// Begin a read-only transaction on checkpoint `name`.
+    pub fn begin_read_checkpoint(&self, name: &str) -> Result<ReadTransaction, Error> {
+        Ok(ReadTransaction {
+            source: ReadSource::Checkpoint(self.pin_checkpoint(name)?),
+            open_tables: vec![],
+        })
+    }
+
+    /// Pin checkpoint `name` so it can't be deleted until the returned `Arc` is dropped.
+    fn pin_checkpoint(&self, name: &str) -> Result<Arc<PinnedCheckpoint>, Error> {
+        let path = checkpoint_path(&self.root_path, name)?;
+        let mut pinned = self.pinned_checkpoints.lock();
+        if let Some(checkpoint) = pinned.get(name).and_then(Weak::upgrade) {
+            return Ok(checkpoint);
+        }
+        if !path.is_dir() {
+            return Err(Error::CheckpointNotFound(name.to_string()));
+        }
+        let checkpoint = Arc::new(PinnedCheckpoint {
+            name: name.to_string(),
+            path,
+        });
+        pinned.retain(|_, pin| pin.strong_count() > 0);
+        pinned.insert(name.to_string(), Arc::downgrade(&checkpoint));
+        Ok(checkpoint)
+    }
+
+    /// Block until all retired snapshots queued for deletion have been deleted.
+    ///
+    /// Snapshots still pinned by a reader aren't queued until the reader is dropped.
+    pub fn wait_for_reclaim(&self) {
+        self.reclaimer.wait();
+    }
+
+    /// Check every table in the committed generation for inconsistencies, and look for stray
+    /// generations.
+    ///
+    /// This waits for any in-progress write transaction, and should be run while no readers are
+    /// open.
+    pub fn verify(&self) -> Result<VerifyReport, Error> {
+        let _txn_lock = self.txn_lock.lock();
+        let mut txn = self.begin_read()?;
+        let mut report = VerifyReport::default();
+        for name in txn.list_tables()? {
+            if let Some(id) = open_or_skip(txn.open_table(&name), &name, &mut report)? {
+                report.inconsistencies.extend(txn.get_table(id)?.verify()?);
+            }
+        }
+        let committed = self.read_snapshot.read().gen;
+        report
+            .inconsistencies
+            .extend(self.stray_generations(&[committed])?);
+        Ok(report)
+    }
+
+    /// Verify the database like `verify`, and repair the tables in a new transaction.
+    ///
+    /// See `Table::repair` for the repairs made. Return the inconsistencies found before
+    /// repairing.
+    pub fn verify_and_repair(&self) -> Result<VerifyReport, Error> {
+        let mut txn = self.begin_transaction()?;
+        let mut report = VerifyReport::default();
+        for name in txn.list_tables()? {
+            if let Some(id) = open_or_skip(txn.open_table(&name), &name, &mut report)? {
+                let table = txn.get_table(id)?;
+                let inconsistencies = table.verify()?;
+                table.repair(&inconsistencies)?;
+                report.inconsistencies.extend(inconsistencies);
+            }
+        }
+        let live = [self.read_snapshot.read().gen, txn.write_snapshot.gen];
+        report
+            .inconsistencies
+            .extend(self.stray_generations(&live)?);
+        txn.commit()?;
+        Ok(report)
+    }
+
+    /// Generations on disk other than `live`, once retired generations have been deleted.
+    fn stray_generations(&self, live: &[Generation]) -> Result<Vec<Inconsistency>, Error> {
+        self.reclaimer.wait();
+        Ok(Self::list_generations(&self.root_path)?
+            .into_iter()
+            .filter(|gen| !live.contains(gen))
+            .map(Inconsistency::StrayGeneration)
+            .collect())
+    }
+
+    /// Recovery actions taken when this database was opened.
+    pub fn recovery_report(&self) -> &RecoveryReport {
+        &self.recovery_report
+    }
+
+    /// Create a writable snapshot of `source` for generation `gen`.
+    ///
+    /// The snapshot starts out retired, so that it is deleted if it is dropped without being
+    /// committed.
+    fn snapshot_generation(&self, source: &Path, gen: Generation) -> Result<Snapshot, Error> {
+        let path = Self::calc_gen_path(&self.root_path, gen);
+        self.backend.snapshot(source, &path, false)?;
+
+        Ok(Snapshot {
+            gen,
+            path,
+            retired: AtomicBool::new(true),
+            reclaimer: self.reclaimer.clone(),
+        })
+    }
+
+    /// Make `snapshot` the latest committed generation.
+    ///
+    /// The caller must hold the transaction lock.
+    pub(crate) fn commit_snapshot(&self, snapshot: Snapshot) -> Result<(), Error> {
+        // Make the snapshot durable, then atomically mark it as committed. If we crash before the
+        // marker is written, recovery discards the snapshot. If we crash after, recovery discards
+        // the previous generation.
+        sync_filesystem(&snapshot.path)?;
+        write_commit_marker(&self.root_path, snapshot.gen)?;
+
+        // Update the read snapshot. This doesn't wait for readers: they hold their own reference
+        // to the snapshot they started on.
+        snapshot.set_retired(false);
+        let prev_snapshot = std::mem::replace(&mut *self.read_snapshot.write(), Arc::new(snapshot));
+
+        // The previous read snapshot is queued for deletion once the last reader pinning it is
+        // dropped (possibly right now).
+        prev_snapshot.set_retired(true);
+        drop(prev_snapshot);
+
+        Ok(())
+    }
+
+    /// List the generations present on disk, in ascending order.
+    fn list_generations(root_path: &Path) -> Result<Vec<Generation>, Error> {
+        let mut generations = vec![];
+        for entry in fs::read_dir(root_path)? {
+            let entry = entry?;
+            if let Some(gen) = entry
+                .file_name()
+                .to_str()
+                .and_then(Generation::from_dir_name)
+            {
+                generations.push(gen);
+            }
         }
+        generations.sort();
+        Ok(generations)
     }
 
     fn calc_gen_path(root_path: &Path, generation: Generation) -> PathBuf {
-        root_path.join(generation.as_ref())
+        root_path.join(generation.dir_name())
     }
 }
@@ -1,18 +1,72 @@
-use btrfsutil::error::BtrfsUtilError;
+use crate::Generation;
 use std::io;
+use std::path::PathBuf;
 
 #[derive(Debug)]
 pub enum Error {
     Oops,
-    Btrfs(BtrfsUtilError),
     Io(io::Error),
+    /// The table was opened by a read transaction, so it can't be modified.
+    ReadOnly,
     Sqlite(sqlite::Error),
-}
-
-impl From<BtrfsUtilError> for Error {
-    fn from(e: BtrfsUtilError) -> Self {
-        Self::Btrfs(e)
-    }
+    /// Both generations exist on disk and there is no commit marker to choose between them.
+    UncleanShutdown,
+    /// The commit marker names a generation that does not exist on disk.
+    MissingGeneration(Generation),
+    /// The database has no commit marker, so readers can't tell which generation is committed.
+    MissingCommitMarker,
+    /// The commit marker could not be parsed.
+    InvalidCommitMarker(String),
+    /// A database using the legacy `tick`/`tock` generations could not be migrated.
+    LegacyMigration(String),
+    /// Checkpoint names must be a single path component.
+    InvalidCheckpointName(String),
+    CheckpointExists(String),
+    CheckpointNotFound(String),
+    /// The checkpoint is being read, so it can't be deleted.
+    CheckpointInUse(String),
+    /// Table names must be non-empty and short enough to fit in a directory name once encoded.
+    InvalidTableName(String),
+    TableExists(String),
+    TableNotFound(String),
+    /// The table has a `TableId` in the current transaction, so it can't be dropped or renamed.
+    TableOpen(String),
+    /// The table's options file could not be parsed, or is from a newer version.
+    InvalidTableOptions(String),
+    KeyTooLarge {
+        len: usize,
+        max: usize,
+    },
+    ValueTooLarge {
+        len: usize,
+        max: usize,
+    },
+    /// A key or value could not be encoded by its codec.
+    Encode(String),
+    /// Comparator names must be non-empty and contain only ASCII alphanumerics and `_`.
+    InvalidComparatorName(String),
+    /// The table was opened or created with a different comparator to the one it uses.
+    ComparatorMismatch {
+        stored: Option<String>,
+        provided: Option<String>,
+    },
+    /// The compression is not supported by this build, because its cargo feature is disabled.
+    UnsupportedCompression(String),
+    /// The stored value of `key` in `table` doesn't match its checksum.
+    Corruption {
+        table: String,
+        key: Vec<u8>,
+    },
+    /// A key or value could not be decoded by its codec.
+    Decode(String),
+    /// The backend recorded in the database root is not recognised.
+    UnknownBackend(String),
+    /// The path is not a snapshot created by the database's backend.
+    NotASnapshot(PathBuf),
+    /// Another process has the database open. Contains its PID, if known.
+    DatabaseLocked {
+        pid: Option<u32>,
+    },
 }
 
 impl From<io::Error> for Error {
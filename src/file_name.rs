@@ -0,0 +1,187 @@
+//! Schemes for naming the value file of a key.
+use crate::util::key_from_hex_bytes;
+use crate::Error;
+use faster_hex::hex_string;
+use std::str::FromStr;
+
+/// Maximum length of a file name on common filesystems.
+pub const MAX_FILE_NAME_LEN: usize = 255;
+
+const BASE64URL_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// How keys are turned into value file names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FileNameScheme {
+    /// Lowercase hex, for keys of up to 127 bytes.
+    #[default]
+    Hex,
+    /// Unpadded base64url, for keys of up to 191 bytes.
+    Base64Url,
+    /// A hash of the key, for keys of any length.
+    ///
+    /// The name of each key's file is stored in the index, so keys whose hashes collide are
+    /// given distinct names.
+    Hashed,
+}
+
+impl FileNameScheme {
+    /// The longest key that can be named by this scheme, if there is a limit.
+    pub fn max_key_len(self) -> Option<usize> {
+        match self {
+            Self::Hex => Some(MAX_FILE_NAME_LEN / 2),
+            Self::Base64Url => Some(MAX_FILE_NAME_LEN * 3 / 4),
+            Self::Hashed => None,
+        }
+    }
+
+    /// File name for `key`, or its first candidate name for the hashed scheme.
+    pub fn file_name(self, key: &[u8]) -> Result<String, Error> {
+        if let Some(max) = self.max_key_len().filter(|max| key.len() > *max) {
+            return Err(Error::KeyTooLarge {
+                len: key.len(),
+                max,
+            });
+        }
+        Ok(match self {
+            Self::Hex => hex_string(key),
+            Self::Base64Url => base64url(key),
+            Self::Hashed => hashed_file_name(key, 0),
+        })
+    }
+
+    /// Key named by `file_name`, or `None` if it isn't a name produced by this scheme.
+    ///
+    /// Hashed names can't be decoded, so are always `None`.
+    pub fn decode(self, file_name: &str) -> Option<Vec<u8>> {
+        let key = match self {
+            Self::Hex => key_from_hex_bytes(file_name.as_bytes()).ok()?,
+            Self::Base64Url => base64url_decode(file_name)?,
+            Self::Hashed => return None,
+        };
+        // Reject names that decode but aren't canonical, like uppercase hex.
+        (self.file_name(&key).ok()? == file_name).then_some(key)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Hex => "hex",
+            Self::Base64Url => "base64url",
+            Self::Hashed => "hashed",
+        }
+    }
+}
+
+impl FromStr for FileNameScheme {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "hex" => Ok(Self::Hex),
+            "base64url" => Ok(Self::Base64Url),
+            "hashed" => Ok(Self::Hashed),
+            _ => Err(Error::InvalidTableOptions(s.to_string())),
+        }
+    }
+}
+
+/// The `attempt`th candidate file name for `key` under the hashed scheme.
+pub fn hashed_file_name(key: &[u8], attempt: usize) -> String {
+    let hash = hex_string(&fnv1a_128(key).to_be_bytes());
+    if attempt == 0 {
+        hash
+    } else {
+        format!("{}-{}", hash, attempt)
+    }
+}
+
+/// 128-bit FNV-1a. The hash determines file names on disk, so it must never change.
+fn fnv1a_128(bytes: &[u8]) -> u128 {
+    const OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME: u128 = 0x0000000001000000000000000000013b;
+    bytes.iter().fold(OFFSET_BASIS, |hash, &byte| {
+        (hash ^ byte as u128).wrapping_mul(PRIME)
+    })
+}
+
+fn base64url(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &byte)| n | (byte as u32) << (16 - 8 * i));
+        // Each full chunk of 3 bytes is 4 characters; a partial chunk of n bytes is n + 1.
+        for i in 0..=chunk.len() {
+            encoded.push(BASE64URL_ALPHABET[(n >> (18 - 6 * i)) as usize & 0x3f] as char);
+        }
+    }
+    encoded
+}
+
+fn base64url_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(encoded.len() * 3 / 4);
+    for chunk in encoded.as_bytes().chunks(4) {
+        // A partial chunk of n characters is n - 1 bytes, and a single character is invalid.
+        let len = chunk.len().checked_sub(1).filter(|len| *len > 0)?;
+        let mut n = 0u32;
+        for (i, &c) in chunk.iter().enumerate() {
+            let digit = BASE64URL_ALPHABET.iter().position(|&a| a == c)? as u32;
+            n |= digit << (18 - 6 * i);
+        }
+        bytes.extend_from_slice(&n.to_be_bytes()[1..=len]);
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn base64url_encoding() {
+        assert_eq!(base64url(b""), "");
+        assert_eq!(base64url(b"f"), "Zg");
+        assert_eq!(base64url(b"fo"), "Zm8");
+        assert_eq!(base64url(b"foo"), "Zm9v");
+        assert_eq!(base64url(b"foob"), "Zm9vYg");
+        assert_eq!(base64url(&[0xfb, 0xff]), "-_8");
+    }
+
+    #[test]
+    fn decode_file_names() {
+        for scheme in [FileNameScheme::Hex, FileNameScheme::Base64Url] {
+            for key in [&b"f"[..], b"fo", b"foo", b"foob", &[0xfb, 0xff]] {
+                let name = scheme.file_name(key).unwrap();
+                assert_eq!(scheme.decode(&name).unwrap(), key);
+            }
+        }
+        assert_eq!(FileNameScheme::Hex.decode("abc"), None);
+        assert_eq!(FileNameScheme::Hex.decode("AB"), None);
+        assert_eq!(FileNameScheme::Hex.decode("zz"), None);
+        assert_eq!(FileNameScheme::Base64Url.decode("Z"), None);
+        assert_eq!(FileNameScheme::Base64Url.decode("Zh"), None);
+        assert_eq!(FileNameScheme::Base64Url.decode("Zm9v.dat"), None);
+        assert_eq!(
+            FileNameScheme::Hashed.decode(&hashed_file_name(b"a", 0)),
+            None
+        );
+    }
+
+    #[test]
+    fn fnv1a_128_known_values() {
+        assert_eq!(fnv1a_128(b""), 0x6c62272e07bb014262b821756295c58d);
+        assert_eq!(fnv1a_128(b"a"), 0xd228cb696f1a8caf78912b704e4a8964);
+    }
+
+    #[test]
+    fn key_length_limits() {
+        for scheme in [FileNameScheme::Hex, FileNameScheme::Base64Url] {
+            let max = scheme.max_key_len().unwrap();
+            assert!(scheme.file_name(&vec![0xff; max]).unwrap().len() <= MAX_FILE_NAME_LEN);
+            assert!(scheme.file_name(&vec![0xff; max + 1]).is_err());
+        }
+        let name = FileNameScheme::Hashed.file_name(&[0; 4096]).unwrap();
+        assert_eq!(name.len(), 32);
+    }
+}
@@ -1,6 +1,7 @@
-use crate::Error;
+use crate::segment::ValueLocation;
+use crate::{Comparator, Error};
 use derivative::Derivative;
-use sqlite::Connection;
+use sqlite::{Connection, OpenFlags, Row};
 use std::path::PathBuf;
 
 /// An index is an ordered list of keys for a table stored as an SQLite database on disk.
@@ -11,28 +12,92 @@ pub struct IndexFile {
     pub(crate) conn: Connection,
     #[allow(dead_code)]
     path: PathBuf,
+    /// Keys are stored as TEXT rather than BLOB, because the table has a custom comparator and
+    /// SQLite only applies collations to text.
+    text_keys: bool,
 }
 
 impl IndexFile {
     pub fn create(path: PathBuf) -> Result<Self, Error> {
+        Self::create_with_comparator(path, None)
+    }
+
+    /// Create an index file whose keys are ordered by `comparator`, or memcmp if `None`.
+    pub fn create_with_comparator(
+        path: PathBuf,
+        comparator: Option<&Comparator>,
+    ) -> Result<Self, Error> {
         let conn = Connection::open(&path)?;
         Self::apply_pragmas(&conn)?;
+        let mut index_file = Self {
+            conn,
+            path,
+            text_keys: false,
+        };
 
-        // Create the index table.
+        let key_column = if let Some(comparator) = comparator {
+            index_file.set_comparator(comparator)?;
+            format!("key TEXT COLLATE {} PRIMARY KEY ASC", comparator.name)
+        } else {
+            "key BLOB PRIMARY KEY ASC".to_string()
+        };
+
+        // Create the index table. The `value` column holds values stored inline, and the
+        // `segment` columns hold the location of values stored in segments. They are NULL for
+        // values stored in files. `file_name` is only used by tables with hashed file names, and
+        // `checksum` by tables with checksums.
         // FIXME(sproul): benchmark default vs WITHOUT ROWID
-        conn.execute(
+        index_file.conn.execute(format!(
             "CREATE TABLE keys (
-                key BLOB PRIMARY KEY ASC
+                {},
+                value BLOB,
+                segment INTEGER,
+                segment_offset INTEGER,
+                value_len INTEGER,
+                file_name TEXT,
+                checksum INTEGER
             ) WITHOUT ROWID",
-        )?;
+            key_column
+        ))?;
 
-        Ok(Self { conn, path })
+        Ok(index_file)
     }
 
     pub fn open(path: PathBuf) -> Result<Self, Error> {
         let conn = Connection::open(&path)?;
         Self::apply_pragmas(&conn)?;
-        Ok(Self { conn, path })
+        Ok(Self {
+            conn,
+            path,
+            text_keys: false,
+        })
+    }
+
+    pub fn open_read_only(path: PathBuf) -> Result<Self, Error> {
+        let conn = Connection::open_with_flags(&path, OpenFlags::new().set_read_only())?;
+        Self::apply_pragmas(&conn)?;
+        Ok(Self {
+            conn,
+            path,
+            text_keys: false,
+        })
+    }
+
+    /// Install `comparator` on the connection, which must be done before accessing the keys of
+    /// an index created with it.
+    pub fn set_comparator(&mut self, comparator: &Comparator) -> Result<(), Error> {
+        comparator.register(self.conn.as_raw())?;
+        self.text_keys = true;
+        Ok(())
+    }
+
+    /// SQL expression for a key bound to the first parameter.
+    pub(crate) fn key_param(&self) -> &'static str {
+        if self.text_keys {
+            "CAST(?1 AS TEXT)"
+        } else {
+            "?1"
+        }
     }
 
     fn apply_pragmas(conn: &Connection) -> Result<(), Error> {
@@ -53,24 +118,185 @@ impl IndexFile {
     /// Ensure that `key` is present in the index file.
     // FIXME(sproul): consider bulking in a transaction
     pub fn put_key(&self, key: &[u8]) -> Result<(), Error> {
-        let mut stmt = self
-            .conn
-            .prepare("INSERT INTO keys VALUES (?1) ON CONFLICT DO NOTHING")?;
+        let mut stmt = self.conn.prepare(format!(
+            "INSERT INTO keys (key) VALUES ({}) ON CONFLICT DO NOTHING",
+            self.key_param()
+        ))?;
         stmt.bind((1, key))?;
         stmt.into_iter().collect::<Result<Vec<_>, _>>()?;
         Ok(())
     }
 
+    /// Ensure that `key` is present in the index file with `value` stored inline, or `None` if
+    /// its value is stored in a file.
+    pub fn put_key_value(&self, key: &[u8], value: Option<&[u8]>) -> Result<(), Error> {
+        let mut stmt = self.conn.prepare(format!(
+            "INSERT INTO keys (key, value) VALUES ({}, ?2)
+                ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            self.key_param()
+        ))?;
+        stmt.bind((1, key))?;
+        stmt.bind((2, value))?;
+        stmt.into_iter().collect::<Result<Vec<_>, _>>()?;
+        Ok(())
+    }
+
+    /// Look up `key`, returning `None` if it is absent.
+    ///
+    /// Otherwise return its inline value, or `None` if its value is stored in a file.
+    pub fn get_value(&self, key: &[u8]) -> Result<Option<Option<Vec<u8>>>, Error> {
+        let mut stmt = self.conn.prepare(format!(
+            "SELECT value FROM keys WHERE key = {}",
+            self.key_param()
+        ))?;
+        stmt.bind((1, key))?;
+        let Some(row) = stmt.into_iter().next().transpose()? else {
+            return Ok(None);
+        };
+        let value: Option<&[u8]> = row.try_read(0)?;
+        Ok(Some(value.map(|slice| slice.to_vec())))
+    }
+
+    /// Ensure that `key` is present in the index file with its value at `location`.
+    pub fn put_key_location(&self, key: &[u8], location: ValueLocation) -> Result<(), Error> {
+        let mut stmt = self.conn.prepare(format!(
+            "INSERT INTO keys (key, segment, segment_offset, value_len) VALUES ({}, ?2, ?3, ?4)
+                ON CONFLICT (key) DO UPDATE SET
+                    segment = excluded.segment,
+                    segment_offset = excluded.segment_offset,
+                    value_len = excluded.value_len",
+            self.key_param()
+        ))?;
+        stmt.bind((1, key))?;
+        stmt.bind((2, location.segment as i64))?;
+        stmt.bind((3, location.offset as i64))?;
+        stmt.bind((4, location.len as i64))?;
+        stmt.into_iter().collect::<Result<Vec<_>, _>>()?;
+        Ok(())
+    }
+
+    /// Look up the segment location of `key`, returning `None` if it is absent.
+    pub fn get_location(&self, key: &[u8]) -> Result<Option<ValueLocation>, Error> {
+        let mut stmt = self.conn.prepare(format!(
+            "SELECT segment, segment_offset, value_len FROM keys WHERE key = {}",
+            self.key_param()
+        ))?;
+        stmt.bind((1, key))?;
+        let Some(row) = stmt.into_iter().next().transpose()? else {
+            return Ok(None);
+        };
+        Ok(Some(read_location(&row, 0)?))
+    }
+
+    /// Total length of the values in each segment, which excludes garbage.
+    pub fn live_bytes_by_segment(&self) -> Result<Vec<(u64, u64)>, Error> {
+        let stmt = self.conn.prepare(
+            "SELECT segment, SUM(value_len) FROM keys WHERE segment IS NOT NULL GROUP BY segment",
+        )?;
+        stmt.into_iter()
+            .map(|row| {
+                let row = row?;
+                Ok((
+                    row.try_read::<i64, _>(0)? as u64,
+                    row.try_read::<i64, _>(1)? as u64,
+                ))
+            })
+            .collect()
+    }
+
+    /// All keys with values in `segment`, and their locations.
+    pub fn keys_in_segment(&self, segment: u64) -> Result<Vec<(Vec<u8>, ValueLocation)>, Error> {
+        let mut stmt = self.conn.prepare(
+            "SELECT CAST(key AS BLOB), segment, segment_offset, value_len FROM keys
+                WHERE segment = ?1",
+        )?;
+        stmt.bind((1, segment as i64))?;
+        stmt.into_iter()
+            .map(|row| {
+                let row = row?;
+                let key = row.try_read::<&[u8], _>(0)?.to_vec();
+                Ok((key, read_location(&row, 1)?))
+            })
+            .collect()
+    }
+
+    /// Record the name of the value file for `key`, which must be present.
+    pub fn set_file_name(&self, key: &[u8], file_name: Option<&str>) -> Result<(), Error> {
+        let mut stmt = self.conn.prepare(format!(
+            "UPDATE keys SET file_name = ?2 WHERE key = {}",
+            self.key_param()
+        ))?;
+        stmt.bind((1, key))?;
+        stmt.bind((2, file_name))?;
+        stmt.into_iter().collect::<Result<Vec<_>, _>>()?;
+        Ok(())
+    }
+
+    /// Look up the name of the value file for `key`, if one has been recorded.
+    pub fn get_file_name(&self, key: &[u8]) -> Result<Option<String>, Error> {
+        let mut stmt = self.conn.prepare(format!(
+            "SELECT file_name FROM keys WHERE key = {}",
+            self.key_param()
+        ))?;
+        stmt.bind((1, key))?;
+        let Some(row) = stmt.into_iter().next().transpose()? else {
+            return Ok(None);
+        };
+        Ok(row.try_read::<Option<&str>, _>(0)?.map(str::to_string))
+    }
+
+    /// Record the checksum of the stored value of `key`, which must be present.
+    pub fn set_checksum(&self, key: &[u8], checksum: Option<u32>) -> Result<(), Error> {
+        let mut stmt = self.conn.prepare(format!(
+            "UPDATE keys SET checksum = ?2 WHERE key = {}",
+            self.key_param()
+        ))?;
+        stmt.bind((1, key))?;
+        stmt.bind((2, checksum.map(i64::from)))?;
+        stmt.into_iter().collect::<Result<Vec<_>, _>>()?;
+        Ok(())
+    }
+
+    /// Look up the checksum of the stored value of `key`, if one has been recorded.
+    pub fn get_checksum(&self, key: &[u8]) -> Result<Option<u32>, Error> {
+        let mut stmt = self.conn.prepare(format!(
+            "SELECT checksum FROM keys WHERE key = {}",
+            self.key_param()
+        ))?;
+        stmt.bind((1, key))?;
+        let Some(row) = stmt.into_iter().next().transpose()? else {
+            return Ok(None);
+        };
+        Ok(row
+            .try_read::<Option<i64>, _>(0)?
+            .map(|checksum| checksum as u32))
+    }
+
+    /// All keys in the index, in order.
+    pub fn keys(&self) -> Result<Vec<Vec<u8>>, Error> {
+        let stmt = self
+            .conn
+            .prepare("SELECT CAST(key AS BLOB) FROM keys ORDER BY key ASC")?;
+        stmt.into_iter()
+            .map(|row| Ok(row?.try_read::<&[u8], _>(0)?.to_vec()))
+            .collect()
+    }
+
     /// Remove `key` from the index file.
     pub fn delete_key(&self, key: &[u8]) -> Result<(), Error> {
-        let mut stmt = self.conn.prepare("DELETE FROM keys WHERE key = ?1")?;
+        let mut stmt = self
+            .conn
+            .prepare(format!("DELETE FROM keys WHERE key = {}", self.key_param()))?;
         stmt.bind((1, key))?;
         stmt.into_iter().collect::<Result<Vec<_>, _>>()?;
         Ok(())
     }
 
     pub fn last_key(&self) -> Result<Option<Vec<u8>>, Error> {
-        let stmt = self.conn.prepare("SELECT MAX(key) FROM keys")?;
+        // Keys are cast back to BLOB so that text keys are returned as raw bytes.
+        let stmt = self
+            .conn
+            .prepare("SELECT CAST(MAX(key) AS BLOB) FROM keys")?;
         for maybe_row in stmt {
             let row = maybe_row?;
             let key: Option<&[u8]> = row.try_read(0)?;
@@ -79,3 +305,12 @@ impl IndexFile {
         Ok(None)
     }
 }
+
+/// Read a `ValueLocation` from the three columns of `row` starting at `column`.
+fn read_location(row: &Row, column: usize) -> Result<ValueLocation, Error> {
+    Ok(ValueLocation {
+        segment: row.try_read::<i64, _>(column)? as u64,
+        offset: row.try_read::<i64, _>(column + 1)? as u64,
+        len: row.try_read::<i64, _>(column + 2)? as u64,
+    })
+}
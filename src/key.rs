@@ -1 +1,262 @@
-use 
+//! Order-preserving key encoding.
+//!
+//! The index sorts keys as byte strings, so composite keys need an encoding whose byte order
+//! matches the order of the values. Each part is encoded so that it is self-delimiting, which
+//! lets tuples be encoded by concatenating their parts:
+//!
+//! - Unsigned integers are fixed-width big-endian.
+//! - Signed integers are fixed-width big-endian with the sign bit flipped.
+//! - Booleans are a single `0` or `1` byte.
+//! - Fixed-size byte arrays are written as-is.
+//! - Byte strings escape `0x00` as `0x00 0xFF` and end with `0x00 0x00`, so a string sorts before
+//!   any longer string it is a prefix of.
+use crate::typed::KeyCodec;
+use crate::Error;
+use std::borrow::Cow;
+
+/// A value that can be part of an order-preserving key.
+pub trait OrderedKey: Sized {
+    /// Append the encoding of `self` to `out`.
+    fn encode_to(&self, out: &mut Vec<u8>);
+
+    /// Decode a value from the front of `input`, advancing it past the bytes consumed.
+    fn decode_from(input: &mut &[u8]) -> Result<Self, Error>;
+}
+
+/// Encode `value` as a key.
+pub fn encode_key<T: OrderedKey>(value: &T) -> Vec<u8> {
+    let mut out = vec![];
+    value.encode_to(&mut out);
+    out
+}
+
+/// Decode a key produced by `encode_key`, which must be consumed entirely.
+pub fn decode_key<T: OrderedKey>(mut bytes: &[u8]) -> Result<T, Error> {
+    let value = T::decode_from(&mut bytes)?;
+    if !bytes.is_empty() {
+        return Err(Error::Decode(format!(
+            "{} trailing bytes after key",
+            bytes.len()
+        )));
+    }
+    Ok(value)
+}
+
+/// Use the order-preserving encoding of `T` as the key of a `TypedTable`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Ordered<T>(pub T);
+
+impl<T: OrderedKey> KeyCodec for Ordered<T> {
+    fn encode_key(&self) -> Result<Cow<'_, [u8]>, Error> {
+        Ok(Cow::Owned(encode_key(&self.0)))
+    }
+
+    fn decode_key(bytes: &[u8]) -> Result<Self, Error> {
+        decode_key(bytes).map(Ordered)
+    }
+}
+
+/// Split `n` bytes off the front of `input`.
+fn take<'a>(input: &mut &'a [u8], n: usize) -> Result<&'a [u8], Error> {
+    if input.len() < n {
+        return Err(Error::Decode(format!(
+            "expected {} bytes, got {}",
+            n,
+            input.len()
+        )));
+    }
+    let (head, tail) = input.split_at(n);
+    *input = tail;
+    Ok(head)
+}
+
+macro_rules! impl_unsigned {
+    ($($int:ty),*) => {
+        $(
+            impl OrderedKey for $int {
+                fn encode_to(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_be_bytes());
+                }
+
+                fn decode_from(input: &mut &[u8]) -> Result<Self, Error> {
+                    let bytes = take(input, std::mem::size_of::<$int>())?;
+                    Ok(<$int>::from_be_bytes(bytes.try_into().unwrap()))
+                }
+            }
+        )*
+    };
+}
+
+impl_unsigned!(u8, u16, u32, u64, u128);
+
+macro_rules! impl_signed {
+    ($($int:ty => $uint:ty),*) => {
+        $(
+            impl OrderedKey for $int {
+                fn encode_to(&self, out: &mut Vec<u8>) {
+                    // Flipping the sign bit moves negative numbers below positive ones.
+                    ((*self as $uint) ^ (1 << (<$uint>::BITS - 1))).encode_to(out);
+                }
+
+                fn decode_from(input: &mut &[u8]) -> Result<Self, Error> {
+                    let flipped = <$uint>::decode_from(input)?;
+                    Ok((flipped ^ (1 << (<$uint>::BITS - 1))) as $int)
+                }
+            }
+        )*
+    };
+}
+
+impl_signed!(i8 => u8, i16 => u16, i32 => u32, i64 => u64, i128 => u128);
+
+impl OrderedKey for bool {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+
+    fn decode_from(input: &mut &[u8]) -> Result<Self, Error> {
+        match take(input, 1)?[0] {
+            0 => Ok(false),
+            1 => Ok(true),
+            b => Err(Error::Decode(format!("invalid bool byte {}", b))),
+        }
+    }
+}
+
+impl<const N: usize> OrderedKey for [u8; N] {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self);
+    }
+
+    fn decode_from(input: &mut &[u8]) -> Result<Self, Error> {
+        Ok(take(input, N)?.try_into().unwrap())
+    }
+}
+
+impl OrderedKey for Vec<u8> {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        for &byte in self {
+            out.push(byte);
+            if byte == 0 {
+                out.push(0xff);
+            }
+        }
+        out.extend_from_slice(&[0, 0]);
+    }
+
+    fn decode_from(input: &mut &[u8]) -> Result<Self, Error> {
+        let mut value = vec![];
+        loop {
+            match take(input, 1)?[0] {
+                0 => match take(input, 1)?[0] {
+                    0 => return Ok(value),
+                    0xff => value.push(0),
+                    b => return Err(Error::Decode(format!("invalid escape byte {}", b))),
+                },
+                b => value.push(b),
+            }
+        }
+    }
+}
+
+impl OrderedKey for String {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        // UTF-8 byte order matches code point order.
+        self.as_bytes().to_vec().encode_to(out)
+    }
+
+    fn decode_from(input: &mut &[u8]) -> Result<Self, Error> {
+        String::from_utf8(Vec::decode_from(input)?).map_err(|e| Error::Decode(e.to_string()))
+    }
+}
+
+macro_rules! impl_tuple {
+    ($($name:ident),+) => {
+        impl<$($name: OrderedKey),+> OrderedKey for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn encode_to(&self, out: &mut Vec<u8>) {
+                let ($($name,)+) = self;
+                $($name.encode_to(out);)+
+            }
+
+            fn decode_from(input: &mut &[u8]) -> Result<Self, Error> {
+                Ok(($($name::decode_from(input)?,)+))
+            }
+        }
+    };
+}
+
+impl_tuple!(A);
+impl_tuple!(A, B);
+impl_tuple!(A, B, C);
+impl_tuple!(A, B, C, D);
+impl_tuple!(A, B, C, D, E);
+impl_tuple!(A, B, C, D, E, F);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_order_preserved<T: OrderedKey + Ord + Clone + std::fmt::Debug>(mut values: Vec<T>) {
+        values.sort();
+        let encoded: Vec<_> = values.iter().map(encode_key).collect();
+        for (value, bytes) in values.iter().zip(&encoded) {
+            assert_eq!(&decode_key::<T>(bytes).unwrap(), value);
+        }
+        for pair in encoded.windows(2) {
+            assert!(pair[0] < pair[1], "{:?} >= {:?}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn integers() {
+        assert_order_preserved(vec![0u64, 1, 255, 256, u64::MAX]);
+        assert_order_preserved(vec![i32::MIN, -256, -1, 0, 1, 255, i32::MAX]);
+        assert_order_preserved(vec![i8::MIN, -1, 0, i8::MAX]);
+    }
+
+    #[test]
+    fn byte_strings() {
+        assert_order_preserved(vec![
+            vec![],
+            vec![0],
+            vec![0, 0],
+            vec![0, 1],
+            vec![1],
+            vec![1, 0],
+            vec![0xff],
+        ]);
+        assert_order_preserved(vec![
+            String::new(),
+            "a".into(),
+            "a\0".into(),
+            "ab".into(),
+            "b".into(),
+        ]);
+    }
+
+    #[test]
+    fn tuples() {
+        assert_order_preserved(vec![
+            (1u64, [0u8; 4]),
+            (1, [0, 0, 0, 1]),
+            (2, [0; 4]),
+            (256, [0xff; 4]),
+        ]);
+        assert_order_preserved(vec![
+            (false, (String::from("a"), -1i64)),
+            (false, (String::from("a"), 0)),
+            (false, (String::from("ab"), -5)),
+            (true, (String::new(), i64::MIN)),
+        ]);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(decode_key::<u32>(&[0, 0, 0]).is_err());
+        assert!(decode_key::<u8>(&[0, 0]).is_err());
+        assert!(decode_key::<bool>(&[2]).is_err());
+        assert!(decode_key::<Vec<u8>>(&[1, 2]).is_err());
+        assert!(decode_key::<Vec<u8>>(&[0, 1, 0, 0]).is_err());
+    }
+}
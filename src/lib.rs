@@ -1,15 +1,46 @@
+pub mod backend;
+pub mod checkpoint;
+pub mod checksum;
+pub mod comparator;
+pub mod compression;
 pub mod cursor;
 pub mod database;
 pub mod error;
+pub mod file_name;
 pub mod index;
+pub mod key;
+pub mod lock;
+pub mod options;
+pub mod read_transaction;
+pub mod reader;
+pub mod reclaim;
+pub mod recovery;
+pub mod segment;
 pub mod table;
 pub mod tests;
 pub mod transaction;
+pub mod typed;
 pub mod util;
+pub mod verify;
+pub mod watch;
 
+pub use backend::{BackendKind, SnapshotBackend};
+pub use checksum::ChecksumMode;
+pub use comparator::Comparator;
+pub use compression::{BtrfsCompression, Compression};
 pub use cursor::Cursor;
 pub use database::{Database, Generation, Snapshot};
 pub use error::Error;
+pub use file_name::FileNameScheme;
 pub use index::IndexFile;
+pub use key::{Ordered, OrderedKey};
+pub use options::{KeyOrdering, TableOptions, ValueStorage};
+pub use read_transaction::{ReadSource, ReadTransaction};
+pub use reader::DatabaseReader;
+pub use recovery::RecoveryReport;
 pub use table::{Table, TableId};
 pub use transaction::Transaction;
+#[cfg(feature = "serde")]
+pub use typed::Bincode;
+pub use typed::{KeyCodec, TypedCursor, TypedTable, ValueCodec};
+pub use verify::{Inconsistency, VerifyReport};
@@ -0,0 +1,62 @@
+//! Advisory lock preventing multiple processes from opening the same database.
+//!
+//! The lock is an exclusive `flock` on a file in the database root, which also records the PID of
+//! the holder for diagnostics. The kernel releases the lock when the file is closed, including
+//! when the process dies, so a stale lock file never prevents the database from being opened.
+use crate::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::fd::AsRawFd;
+use std::path::Path;
+
+const LOCK_FILENAME: &str = "lock";
+
+/// An exclusive lock on a database root, released when dropped.
+#[derive(Debug)]
+pub struct LockFile {
+    _file: File,
+}
+
+impl LockFile {
+    /// Acquire the lock on `root_path`, waiting for any other holder to release it.
+    pub fn lock(root_path: &Path) -> Result<Self, Error> {
+        Self::acquire(root_path, true)
+    }
+
+    /// Acquire the lock on `root_path`, failing immediately if it is held by someone else.
+    pub fn try_lock(root_path: &Path) -> Result<Self, Error> {
+        Self::acquire(root_path, false)
+    }
+
+    fn acquire(root_path: &Path, block: bool) -> Result<Self, Error> {
+        let path = root_path.join(LOCK_FILENAME);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        let operation = if block {
+            libc::LOCK_EX
+        } else {
+            libc::LOCK_EX | libc::LOCK_NB
+        };
+        if unsafe { libc::flock(file.as_raw_fd(), operation) } != 0 {
+            let e = io::Error::last_os_error();
+            if e.kind() == io::ErrorKind::WouldBlock {
+                // The holder may not have written its PID yet.
+                let pid = fs::read_to_string(&path)
+                    .ok()
+                    .and_then(|contents| contents.trim().parse().ok());
+                return Err(Error::DatabaseLocked { pid });
+            }
+            return Err(e.into());
+        }
+
+        file.set_len(0)?;
+        write!(file, "{}", std::process::id())?;
+
+        Ok(Self { _file: file })
+    }
+}
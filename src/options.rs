@@ -0,0 +1,223 @@
+//! Per-table options, persisted alongside the table's data.
+//!
+//! Options are written to a small text file of `key = value` lines when the table is created and
+//! read back whenever it is opened, so every process accessing the table agrees on them.
+use crate::{ChecksumMode, Comparator, Compression, Error, FileNameScheme};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Like all internal files in a table, the name contains a `.`, which value file names never do.
+pub(crate) const OPTIONS_FILENAME: &str = "options.txt";
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TableOptions {
+    pub key_ordering: KeyOrdering,
+    /// Maximum key length in bytes, or `None` for no limit.
+    pub max_key_size: Option<usize>,
+    /// Maximum value length in bytes, or `None` for no limit.
+    pub max_value_size: Option<usize>,
+    pub value_storage: ValueStorage,
+    /// Allow many values per key, kept in sorted order.
+    pub dup_sort: bool,
+    /// Number of directory levels above each value file, each named by the next two hex digits
+    /// of the key. Zero keeps all value files in the table directory.
+    pub fan_out: u8,
+    /// How value files are named after their keys.
+    pub file_names: FileNameScheme,
+    /// Checksums of stored values, verified on every read.
+    pub checksum: ChecksumMode,
+    pub compression: Compression,
+}
+
+/// The order in which keys are iterated.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum KeyOrdering {
+    /// Byte-wise lexicographic order.
+    #[default]
+    Lexicographic,
+    /// Order defined by the `Comparator` with this name.
+    Custom(String),
+}
+
+/// Where values are stored on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ValueStorage {
+    /// One file per key.
+    #[default]
+    File,
+    /// Values of at most `threshold` bytes are stored in the index, and larger ones in files.
+    Inline { threshold: usize },
+    /// Values are appended to segment files of roughly `segment_size` bytes.
+    Packed { segment_size: u64 },
+}
+
+impl TableOptions {
+    fn file_path(table_path: &Path) -> PathBuf {
+        table_path.join(OPTIONS_FILENAME)
+    }
+
+    /// Read the options for the table at `table_path`.
+    ///
+    /// Tables created before options were persisted don't have an options file and use the
+    /// defaults.
+    pub fn read(table_path: &Path) -> Result<Self, Error> {
+        match fs::read_to_string(Self::file_path(table_path)) {
+            Ok(contents) => contents.parse(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write these options to the table at `table_path`.
+    pub fn write(&self, table_path: &Path) -> Result<(), Error> {
+        fs::write(Self::file_path(table_path), self.to_string())?;
+        Ok(())
+    }
+
+    /// Check that `key` and `value` are within the size limits of the table.
+    pub fn check_put(&self, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        if let Some(max) = self.max_key_size.filter(|max| key.len() > *max) {
+            return Err(Error::KeyTooLarge {
+                len: key.len(),
+                max,
+            });
+        }
+        if let Some(max) = self.max_value_size.filter(|max| value.len() > *max) {
+            return Err(Error::ValueTooLarge {
+                len: value.len(),
+                max,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for TableOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "key_ordering = {}", self.key_ordering)?;
+        writeln!(f, "max_key_size = {}", format_limit(self.max_key_size))?;
+        writeln!(f, "max_value_size = {}", format_limit(self.max_value_size))?;
+        writeln!(f, "value_storage = {}", self.value_storage)?;
+        writeln!(f, "dup_sort = {}", self.dup_sort)?;
+        writeln!(f, "fan_out = {}", self.fan_out)?;
+        writeln!(f, "file_names = {}", self.file_names.as_str())?;
+        writeln!(f, "checksum = {}", self.checksum.as_str())?;
+        writeln!(f, "compression = {}", self.compression)
+    }
+}
+
+impl FromStr for TableOptions {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidTableOptions(s.to_string());
+
+        let mut options = Self::default();
+        for line in s.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let (key, value) = line.split_once('=').ok_or_else(invalid)?;
+            let value = value.trim();
+            match key.trim() {
+                "key_ordering" => options.key_ordering = value.parse()?,
+                "max_key_size" => options.max_key_size = parse_limit(value).ok_or_else(invalid)?,
+                "max_value_size" => {
+                    options.max_value_size = parse_limit(value).ok_or_else(invalid)?
+                }
+                "value_storage" => options.value_storage = value.parse()?,
+                "dup_sort" => options.dup_sort = value.parse().map_err(|_| invalid())?,
+                "fan_out" => options.fan_out = value.parse().map_err(|_| invalid())?,
+                "file_names" => options.file_names = value.parse()?,
+                "checksum" => options.checksum = value.parse()?,
+                "compression" => options.compression = value.parse()?,
+                // Options added by a newer version of the crate must not be silently ignored.
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn format_limit(limit: Option<usize>) -> String {
+    limit.map_or_else(|| "none".to_string(), |n| n.to_string())
+}
+
+fn parse_limit(s: &str) -> Option<Option<usize>> {
+    if s == "none" {
+        Some(None)
+    } else {
+        s.parse().ok().map(Some)
+    }
+}
+
+impl KeyOrdering {
+    /// The name of the custom comparator, if any.
+    pub fn comparator_name(&self) -> Option<&str> {
+        match self {
+            Self::Lexicographic => None,
+            Self::Custom(name) => Some(name),
+        }
+    }
+}
+
+impl std::fmt::Display for KeyOrdering {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Lexicographic => write!(f, "lexicographic"),
+            Self::Custom(name) => write!(f, "custom:{}", name),
+        }
+    }
+}
+
+impl FromStr for KeyOrdering {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s.split_once(':') {
+            None if s == "lexicographic" => Ok(Self::Lexicographic),
+            Some(("custom", name)) => {
+                Comparator::validate_name(name)?;
+                Ok(Self::Custom(name.to_string()))
+            }
+            _ => Err(Error::InvalidTableOptions(s.to_string())),
+        }
+    }
+}
+
+impl ValueStorage {
+    /// Check whether a value of `len` bytes should be stored in the index.
+    pub fn is_inline(self, len: usize) -> bool {
+        match self {
+            Self::File | Self::Packed { .. } => false,
+            Self::Inline { threshold } => len <= threshold,
+        }
+    }
+}
+
+impl std::fmt::Display for ValueStorage {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::File => write!(f, "file"),
+            Self::Inline { threshold } => write!(f, "inline:{}", threshold),
+            Self::Packed { segment_size } => write!(f, "packed:{}", segment_size),
+        }
+    }
+}
+
+impl FromStr for ValueStorage {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidTableOptions(s.to_string());
+        match s.split_once(':') {
+            None if s == "file" => Ok(Self::File),
+            Some(("inline", threshold)) => Ok(Self::Inline {
+                threshold: threshold.parse().map_err(|_| invalid())?,
+            }),
+            Some(("packed", segment_size)) => Ok(Self::Packed {
+                segment_size: segment_size.parse().map_err(|_| invalid())?,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
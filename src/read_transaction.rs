@@ -0,0 +1,106 @@
+use crate::checkpoint::PinnedCheckpoint;
+use crate::reader::PinnedSnapshot;
+use crate::table::{list_tables, table_dir_path};
+use crate::{Comparator, Cursor, Error, Generation, Snapshot, Table, TableId};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// A read-only view of a committed generation or a checkpoint.
+///
+/// Unlike `Transaction` this does not take the transaction lock or create a snapshot, so it can
+/// run concurrently with a writer. Tables are opened directly from the snapshot being read.
+#[derive(Debug)]
+pub struct ReadTransaction {
+    pub(crate) source: ReadSource,
+    pub(crate) open_tables: Vec<Table>,
+}
+
+/// The snapshot that a `ReadTransaction` reads from.
+#[derive(Debug)]
+pub enum ReadSource {
+    /// A committed generation, pinned on disk until the `ReadTransaction` is dropped.
+    Generation(Arc<Snapshot>),
+    /// A committed generation of a database written by another process, pinned by a lock.
+    Pinned(Arc<PinnedSnapshot>),
+    /// A named checkpoint, pinned until the `ReadTransaction` is dropped.
+    Checkpoint(Arc<PinnedCheckpoint>),
+}
+
+impl ReadSource {
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Generation(snapshot) => &snapshot.path,
+            Self::Pinned(snapshot) => &snapshot.path,
+            Self::Checkpoint(checkpoint) => &checkpoint.path,
+        }
+    }
+}
+
+impl ReadTransaction {
+    /// The generation this transaction is reading from, or `None` if reading from a checkpoint.
+    pub fn generation(&self) -> Option<Generation> {
+        match &self.source {
+            ReadSource::Generation(snapshot) => Some(snapshot.gen),
+            ReadSource::Pinned(snapshot) => Some(snapshot.gen),
+            ReadSource::Checkpoint(_) => None,
+        }
+    }
+
+    /// The checkpoint this transaction is reading from, if any.
+    pub fn checkpoint_name(&self) -> Option<&str> {
+        match &self.source {
+            ReadSource::Generation(_) | ReadSource::Pinned(_) => None,
+            ReadSource::Checkpoint(checkpoint) => Some(&checkpoint.name),
+        }
+    }
+
+    /// Path to the directory for a table, which must exist.
+    fn table_path(&self, name: &str) -> Result<PathBuf, Error> {
+        let path = table_dir_path(self.source.path(), name)?;
+        if !path.is_dir() {
+            return Err(Error::TableNotFound(name.to_string()));
+        }
+        Ok(path)
+    }
+
+    /// List the names of all tables in the snapshot.
+    pub fn list_tables(&self) -> Result<Vec<String>, Error> {
+        list_tables(self.source.path())
+    }
+
+    pub fn open_table(&mut self, name: &str) -> Result<TableId, Error> {
+        let table = Table::open_read_only(self.table_path(name)?)?;
+        let id = TableId::new(self.open_tables.len());
+        self.open_tables.push(table);
+        Ok(id)
+    }
+
+    /// Open a table created with a custom comparator.
+    pub fn open_table_with_comparator(
+        &mut self,
+        name: &str,
+        comparator: &Comparator,
+    ) -> Result<TableId, Error> {
+        let table = Table::open_with_comparator(self.table_path(name)?, Some(comparator), true)?;
+        let id = TableId::new(self.open_tables.len());
+        self.open_tables.push(table);
+        Ok(id)
+    }
+
+    pub fn get_table(&self, id: TableId) -> Result<&Table, Error> {
+        self.open_tables.get(id.id).ok_or(Error::Oops)
+    }
+
+    pub fn get(&self, table: &Table, key: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        table.get(key)
+    }
+
+    /// Read all values for `key`, in sorted order.
+    pub fn get_all(&self, table: &Table, key: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+        table.get_all(key)
+    }
+
+    pub fn cursor<'b>(&'b self, table: &'b Table) -> Result<Cursor<'b>, Error> {
+        Cursor::new(table)
+    }
+}
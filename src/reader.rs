@@ -0,0 +1,125 @@
+//! Read-only access to a database from processes other than the writer.
+//!
+//! A reader process can't rely on the writer's in-memory reference counts to keep the generation
+//! it is reading alive. Instead it takes a shared `flock` on the generation's directory, and the
+//! writer's reclaimer takes an exclusive lock before deleting a generation, deferring the deletion
+//! while any reader holds it.
+use crate::recovery::read_commit_marker;
+use crate::watch::CommitWatcher;
+use crate::{Error, Generation, ReadSource, ReadTransaction};
+use parking_lot::Mutex;
+use std::fs::{self, File};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Number of times to retry pinning the committed generation if it is deleted concurrently.
+const PIN_ATTEMPTS: usize = 32;
+
+/// A committed generation pinned by a shared lock on its directory.
+#[derive(Debug)]
+pub struct PinnedSnapshot {
+    pub(crate) gen: Generation,
+    pub(crate) path: PathBuf,
+    /// Directory holding the shared lock, released when dropped.
+    _dir: File,
+}
+
+impl PinnedSnapshot {
+    /// Pin the generation named by the commit marker in `root_path`.
+    fn pin_committed(root_path: &Path) -> Result<Self, Error> {
+        let mut gen = None;
+        for _ in 0..PIN_ATTEMPTS {
+            let committed = read_commit_marker(root_path)?.ok_or(Error::MissingCommitMarker)?;
+            gen = Some(committed);
+            let path = root_path.join(committed.dir_name());
+
+            let dir = match File::open(&path) {
+                Ok(dir) => dir,
+                // Deleted after a newer commit, re-read the marker.
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            if unsafe { libc::flock(dir.as_raw_fd(), libc::LOCK_SH) } != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+
+            // The writer may have deleted the generation between us reading the marker and taking
+            // the lock, so check that the directory is still in place.
+            let locked = dir.metadata()?;
+            match fs::metadata(&path) {
+                Ok(current) if current.dev() == locked.dev() && current.ino() == locked.ino() => {
+                    return Ok(Self {
+                        gen: committed,
+                        path,
+                        _dir: dir,
+                    });
+                }
+                Ok(_) => continue,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Err(gen.map_or(Error::MissingCommitMarker, Error::MissingGeneration))
+    }
+}
+
+/// Try to take an exclusive lock on the snapshot directory at `path`.
+///
+/// Returns `None` if the snapshot is pinned by a reader. The lock is held until the returned file
+/// is dropped.
+pub(crate) fn try_lock_exclusive(path: &Path) -> io::Result<Option<File>> {
+    let dir = File::open(path)?;
+    if unsafe { libc::flock(dir.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        let e = io::Error::last_os_error();
+        if e.kind() == io::ErrorKind::WouldBlock {
+            return Ok(None);
+        }
+        return Err(e);
+    }
+    Ok(Some(dir))
+}
+
+/// Read-only handle to a database that is written by another process.
+///
+/// New read transactions observe the latest commit, while existing ones keep reading the
+/// generation they started on. The most recently observed generation stays pinned until a newer
+/// commit is observed.
+#[derive(Debug)]
+pub struct DatabaseReader {
+    root_path: PathBuf,
+    current: Mutex<Arc<PinnedSnapshot>>,
+    watcher: CommitWatcher,
+}
+
+impl DatabaseReader {
+    pub(crate) fn open(root_path: PathBuf) -> Result<Self, Error> {
+        // Start watching before reading the marker so that no commit is missed.
+        let watcher = CommitWatcher::new(&root_path)?;
+        let current = Mutex::new(Arc::new(PinnedSnapshot::pin_committed(&root_path)?));
+        Ok(Self {
+            root_path,
+            current,
+            watcher,
+        })
+    }
+
+    /// Begin a read-only transaction on the most recently committed generation.
+    pub fn begin_read(&self) -> Result<ReadTransaction, Error> {
+        let mut current = self.current.lock();
+        if self.watcher.poll()? {
+            *current = Arc::new(PinnedSnapshot::pin_committed(&self.root_path)?);
+        }
+        Ok(ReadTransaction {
+            source: ReadSource::Pinned(current.clone()),
+            open_tables: vec![],
+        })
+    }
+
+    /// The most recently observed committed generation.
+    pub fn generation(&self) -> Generation {
+        self.current.lock().gen
+    }
+}
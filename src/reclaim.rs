@@ -0,0 +1,139 @@
+//! Background deletion of obsolete snapshots.
+//!
+//! Deleting a snapshot takes time proportional to its size, so rather than doing it inline in
+//! `Transaction::commit` or when a transaction is aborted, retired snapshots are sent to a
+//! dedicated thread. Deletions that haven't finished when the process exits are resumed when the
+//! database is next opened, because every generation other than the committed one is discarded.
+//!
+//! Snapshots pinned by a `DatabaseReader` in another process can't be deleted until it releases
+//! them. These are set aside and retried periodically.
+use crate::backend::SnapshotBackend;
+use crate::reader::try_lock_exclusive;
+use crate::Error;
+use parking_lot::{Condvar, Mutex};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Maximum number of deletions that can be queued before `Reclaimer::reclaim` blocks.
+pub const RECLAIM_QUEUE_SIZE: usize = 64;
+
+/// How often to retry deleting snapshots pinned by readers in other processes.
+pub const PINNED_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Handle to the reclaimer thread.
+///
+/// The thread exits once every handle has been dropped and the queue has been drained.
+#[derive(Debug, Clone)]
+pub struct Reclaimer {
+    sender: SyncSender<PathBuf>,
+    pending: Arc<Pending>,
+    backend: Arc<dyn SnapshotBackend>,
+}
+
+/// Number of deletions that have been queued but not yet completed or deferred.
+#[derive(Debug, Default)]
+struct Pending {
+    count: Mutex<usize>,
+    done: Condvar,
+}
+
+impl Pending {
+    fn increment(&self) {
+        *self.count.lock() += 1;
+    }
+
+    fn decrement(&self) {
+        let mut count = self.count.lock();
+        *count -= 1;
+        if *count == 0 {
+            self.done.notify_all();
+        }
+    }
+}
+
+impl Reclaimer {
+    pub fn spawn(backend: Arc<dyn SnapshotBackend>) -> Result<Self, Error> {
+        let (sender, receiver) = sync_channel(RECLAIM_QUEUE_SIZE);
+        let pending = Arc::new(Pending::default());
+
+        let thread_pending = pending.clone();
+        let thread_backend = backend.clone();
+        thread::Builder::new()
+            .name("butter_db_reclaim".into())
+            .spawn(move || Self::run(receiver, thread_pending, &*thread_backend))?;
+
+        Ok(Self {
+            sender,
+            pending,
+            backend,
+        })
+    }
+
+    /// Queue the snapshot at `path` for deletion.
+    ///
+    /// Blocks if the queue is full.
+    pub fn reclaim(&self, path: PathBuf) {
+        self.pending.increment();
+        if let Err(e) = self.sender.send(path) {
+            // The reclaimer thread has died, so delete the snapshot inline.
+            try_delete_snapshot(&*self.backend, &e.0);
+            self.pending.decrement();
+        }
+    }
+
+    /// Block until every queued deletion has completed, or been deferred because the snapshot is
+    /// pinned by another process.
+    pub fn wait(&self) {
+        let mut count = self.pending.count.lock();
+        while *count > 0 {
+            self.pending.done.wait(&mut count);
+        }
+    }
+
+    fn run(receiver: Receiver<PathBuf>, pending: Arc<Pending>, backend: &dyn SnapshotBackend) {
+        let mut pinned = vec![];
+        loop {
+            let next = if pinned.is_empty() {
+                receiver.recv().map_err(|_| RecvTimeoutError::Disconnected)
+            } else {
+                receiver.recv_timeout(PINNED_RETRY_INTERVAL)
+            };
+            match next {
+                Ok(path) => {
+                    if !try_delete_snapshot(backend, &path) {
+                        pinned.push(path);
+                    }
+                    pending.decrement();
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    pinned.retain(|path| !try_delete_snapshot(backend, path));
+                }
+                // Snapshots that are still pinned are queued for deletion again the next time the
+                // database is opened.
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+    }
+}
+
+/// Delete the snapshot at `path`, returning `false` if it is pinned by a reader.
+fn try_delete_snapshot(backend: &dyn SnapshotBackend, path: &Path) -> bool {
+    // Hold an exclusive lock for the duration of the deletion, so that a reader can't pin the
+    // snapshot part way through.
+    let _lock = match try_lock_exclusive(path) {
+        Ok(Some(lock)) => Some(lock),
+        Ok(None) => return false,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return true,
+        // Try the deletion anyway, it will most likely fail with the same error.
+        Err(_) => None,
+    };
+
+    // Errors can't be reported to anyone useful from here. Any snapshot left behind is queued
+    // for deletion again the next time the database is opened.
+    let _ = backend.delete(path);
+    true
+}
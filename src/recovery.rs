@@ -0,0 +1,125 @@
+//! Commit marker and crash recovery.
+//!
+//! The commit marker is a small file in the database root naming the most recently committed
+//! generation. It is written atomically (write to a temporary file, fsync, rename, fsync the
+//! directory) as the last step of `Transaction::commit`, so on startup it tells us which of the
+//! generations on disk holds fully committed data.
+use crate::{Error, Generation};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+
+pub(crate) const COMMIT_MARKER_FILENAME: &str = "committed";
+const COMMIT_MARKER_TMP_FILENAME: &str = "committed.tmp";
+
+/// Names of the two generations used by databases created before generations were numbered.
+const LEGACY_GENERATIONS: [&str; 2] = ["tick", "tock"];
+
+/// Summary of the recovery actions taken when opening a database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecoveryReport {
+    /// The database was shut down cleanly, or newly created.
+    Clean,
+    /// Generations other than the committed one were present after an unclean shutdown.
+    ///
+    /// The generations not named by the commit marker were queued for deletion. Each was either
+    /// the write snapshot of an interrupted transaction, or a previous generation awaiting
+    /// deletion.
+    Recovered {
+        committed: Generation,
+        discarded: Vec<Generation>,
+    },
+}
+
+fn commit_marker_path(root_path: &Path) -> PathBuf {
+    root_path.join(COMMIT_MARKER_FILENAME)
+}
+
+/// Read the generation named by the commit marker, if any.
+pub fn read_commit_marker(root_path: &Path) -> Result<Option<Generation>, Error> {
+    let contents = match fs::read_to_string(commit_marker_path(root_path)) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    contents
+        .trim()
+        .parse()
+        .map(Some)
+        .map_err(|_| Error::InvalidCommitMarker(contents))
+}
+
+/// Convert a database from the legacy `tick`/`tock` layout to numbered generations.
+///
+/// The committed legacy generation becomes generation 0, and the other one, if present, becomes
+/// generation 1 so that recovery discards it. Each step can be repeated, so a migration
+/// interrupted by a crash is completed on the next open.
+pub fn migrate_legacy_generations(root_path: &Path) -> Result<(), Error> {
+    let marker = match fs::read_to_string(commit_marker_path(root_path)) {
+        Ok(contents) => Some(contents.trim().to_string()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    let present = LEGACY_GENERATIONS
+        .into_iter()
+        .filter(|name| root_path.join(name).exists())
+        .collect::<Vec<_>>();
+
+    let committed = match (marker.as_deref(), present.as_slice()) {
+        (Some(name), _) if LEGACY_GENERATIONS.contains(&name) => name,
+        // Databases created before the commit marker was introduced don't have one.
+        (None, [only]) => only,
+        (None, [_, _]) => return Err(Error::UncleanShutdown),
+        _ => return Ok(()),
+    };
+
+    let committed_path = root_path.join(Generation(0).dir_name());
+    if present.contains(&committed) {
+        if committed_path.exists() {
+            return Err(Error::LegacyMigration(format!(
+                "both {} and {} exist",
+                committed,
+                committed_path.display()
+            )));
+        }
+        fs::rename(root_path.join(committed), &committed_path)?;
+    } else if !committed_path.exists() {
+        return Err(Error::LegacyMigration(format!(
+            "committed generation {} is missing",
+            committed
+        )));
+    }
+    if let Some(other) = present.into_iter().find(|name| *name != committed) {
+        fs::rename(
+            root_path.join(other),
+            root_path.join(Generation(1).dir_name()),
+        )?;
+    }
+    write_commit_marker(root_path, Generation(0))
+}
+
+/// Atomically record `gen` as the most recently committed generation.
+pub fn write_commit_marker(root_path: &Path, gen: Generation) -> Result<(), Error> {
+    let tmp_path = root_path.join(COMMIT_MARKER_TMP_FILENAME);
+    let mut tmp_file = File::create(&tmp_path)?;
+    tmp_file.write_all(gen.to_string().as_bytes())?;
+    tmp_file.sync_all()?;
+    drop(tmp_file);
+
+    fs::rename(&tmp_path, commit_marker_path(root_path))?;
+    File::open(root_path)?.sync_all()?;
+    Ok(())
+}
+
+/// Flush all data for the filesystem containing `path` to disk.
+///
+/// Used to ensure that a write snapshot is durable before the commit marker points at it.
+pub fn sync_filesystem(path: &Path) -> Result<(), Error> {
+    let file = File::open(path)?;
+    let res = unsafe { libc::syncfs(file.as_raw_fd()) };
+    if res != 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok(())
+}
@@ -0,0 +1,163 @@
+//! Packed segment files for tables with `ValueStorage::Packed`.
+//!
+//! Values are appended to numbered segment files in the table directory, and their locations are
+//! recorded in the index. Overwritten and deleted values are left in place as garbage until the
+//! table is compacted. Segments are only ever appended to or deleted, so generations sharing them
+//! through a snapshot still see their own values.
+use crate::Error;
+use parking_lot::Mutex;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+
+const SEGMENT_PREFIX: &str = "segment-";
+const SEGMENT_SUFFIX: &str = ".dat";
+
+/// The position of a value within a segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValueLocation {
+    pub segment: u64,
+    pub offset: u64,
+    pub len: u64,
+}
+
+#[derive(Debug)]
+pub struct Segments {
+    table_path: PathBuf,
+    /// Segments are closed once they reach this size.
+    segment_size: u64,
+    /// The segment being appended to, opened on the first write.
+    active: Mutex<Option<ActiveSegment>>,
+}
+
+#[derive(Debug)]
+struct ActiveSegment {
+    id: u64,
+    file: File,
+    len: u64,
+}
+
+impl Segments {
+    pub fn new(table_path: PathBuf, segment_size: u64) -> Self {
+        Self {
+            table_path,
+            segment_size,
+            active: Mutex::new(None),
+        }
+    }
+
+    fn segment_path(&self, id: u64) -> PathBuf {
+        self.table_path
+            .join(format!("{}{}{}", SEGMENT_PREFIX, id, SEGMENT_SUFFIX))
+    }
+
+    /// List the IDs of all segments in the table, in ascending order.
+    pub fn list(&self) -> Result<Vec<u64>, Error> {
+        list_segments(&self.table_path)
+    }
+
+    /// Size in bytes of the segment with `id`.
+    pub fn segment_len(&self, id: u64) -> Result<u64, Error> {
+        Ok(fs::metadata(self.segment_path(id))?.len())
+    }
+
+    /// Append `bytes` to the active segment, starting a new one if it is full.
+    pub fn append(&self, bytes: &[u8]) -> Result<ValueLocation, Error> {
+        let mut active = self.active.lock();
+        let needs_new_segment = match &*active {
+            Some(segment) => {
+                segment.len > 0 && segment.len + bytes.len() as u64 > self.segment_size
+            }
+            None => true,
+        };
+        if needs_new_segment {
+            *active = Some(self.open_segment(active.as_ref().map(|segment| segment.id))?);
+        }
+        let segment = active.as_mut().ok_or(Error::Oops)?;
+
+        segment.file.write_all(bytes)?;
+        let location = ValueLocation {
+            segment: segment.id,
+            offset: segment.len,
+            len: bytes.len() as u64,
+        };
+        segment.len += bytes.len() as u64;
+        Ok(location)
+    }
+
+    /// Open a segment to append to.
+    ///
+    /// On the first write this continues the newest existing segment if it has room. Otherwise,
+    /// including when `previous` is full, a new segment is created after the newest one.
+    fn open_segment(&self, previous: Option<u64>) -> Result<ActiveSegment, Error> {
+        let newest = self.list()?.last().copied();
+        if let (None, Some(id)) = (previous, newest) {
+            let len = self.segment_len(id)?;
+            if len < self.segment_size {
+                let file = OpenOptions::new()
+                    .append(true)
+                    .open(self.segment_path(id))?;
+                return Ok(ActiveSegment { id, file, len });
+            }
+        }
+        let id = newest.map_or(0, |id| id + 1);
+        let file = OpenOptions::new()
+            .append(true)
+            .create_new(true)
+            .open(self.segment_path(id))?;
+        Ok(ActiveSegment { id, file, len: 0 })
+    }
+
+    /// Stop appending to the active segment, so that later writes go to a new one.
+    pub fn rotate(&self) -> Result<(), Error> {
+        let mut active = self.active.lock();
+        let previous = match active.take() {
+            Some(segment) => segment.id,
+            None => match self.list()?.last() {
+                Some(id) => *id,
+                None => return Ok(()),
+            },
+        };
+        *active = Some(self.open_segment(Some(previous))?);
+        Ok(())
+    }
+
+    /// Read the value at `location`.
+    pub fn read(&self, location: ValueLocation) -> Result<Vec<u8>, Error> {
+        let file = File::open(self.segment_path(location.segment))?;
+        let mut bytes = vec![0; location.len as usize];
+        file.read_exact_at(&mut bytes, location.offset)?;
+        Ok(bytes)
+    }
+
+    /// Delete the segment with `id`, which must not contain any live values.
+    pub fn delete(&self, id: u64) -> Result<(), Error> {
+        let mut active = self.active.lock();
+        if active.as_ref().is_some_and(|segment| segment.id == id) {
+            *active = None;
+        }
+        fs::remove_file(self.segment_path(id))?;
+        Ok(())
+    }
+}
+
+/// Parse the ID out of a segment file name, or return `None` if it isn't one.
+pub(crate) fn parse_segment_name(name: &str) -> Option<u64> {
+    name.strip_prefix(SEGMENT_PREFIX)
+        .and_then(|rest| rest.strip_suffix(SEGMENT_SUFFIX))
+        .and_then(|id| id.parse().ok())
+}
+
+/// List the IDs of the segments in the table at `table_path`, in ascending order.
+pub fn list_segments(table_path: &Path) -> Result<Vec<u64>, Error> {
+    let mut ids = vec![];
+    for entry in fs::read_dir(table_path)? {
+        let entry = entry?;
+        if let Some(id) = entry.file_name().to_str().and_then(parse_segment_name) {
+            ids.push(id);
+        }
+    }
+    ids.sort();
+    Ok(ids)
+}
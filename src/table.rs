@@ -1,6 +1,19 @@
-use crate::IndexFile;
-use faster_hex::hex_string;
-use std::path::PathBuf;
+use crate::file_name::hashed_file_name;
+use crate::segment::Segments;
+use crate::verify::list_value_files;
+use crate::{
+    Comparator, Error, FileNameScheme, Inconsistency, IndexFile, TableOptions, ValueStorage,
+};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Name of the index file in a table's directory.
+pub(crate) const INDEX_FILENAME: &str = "index.sqlite";
+
+/// Name of the directory that value files are staged in while changing a table's fan-out.
+pub(crate) const FAN_OUT_STAGING_DIRNAME: &str = ".fan_out.tmp";
 
 /// Index into `open_tables`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -8,25 +21,628 @@ pub struct TableId {
     pub id: usize,
 }
 
-/// A table is a directory under `/{generation}/{table_name}` containing an index file.
+/// A table is a directory under `/{generation}/{encoded_table_name}` containing an index file.
 #[derive(Debug)]
 pub struct Table {
     pub path: PathBuf,
     pub index_file: IndexFile,
+    pub options: TableOptions,
+    /// Segment files, for tables with packed value storage.
+    pub(crate) segments: Option<Segments>,
+    /// Whether the table belongs to a read transaction, and so must not be modified.
+    pub(crate) read_only: bool,
 }
 
 impl Table {
-    /// Path to the file for a key.
+    pub fn new(path: PathBuf, index_file: IndexFile, options: TableOptions) -> Self {
+        let segments = match options.value_storage {
+            ValueStorage::Packed { segment_size } => {
+                Some(Segments::new(path.clone(), segment_size))
+            }
+            ValueStorage::File | ValueStorage::Inline { .. } => None,
+        };
+        Table {
+            path,
+            index_file,
+            options,
+            segments,
+            read_only: false,
+        }
+    }
+
+    /// Path to the index file for a table.
+    pub fn index_file_path(table_path: &Path) -> PathBuf {
+        table_path.join(INDEX_FILENAME)
+    }
+
+    /// Open an existing table at `path` for reading and writing.
+    pub fn open(path: PathBuf) -> Result<Self, Error> {
+        Self::open_with_comparator(path, None, false)
+    }
+
+    /// Open an existing table at `path` without the ability to modify it.
+    pub fn open_read_only(path: PathBuf) -> Result<Self, Error> {
+        Self::open_with_comparator(path, None, true)
+    }
+
+    /// Open an existing table at `path`, which must have been created with `comparator`.
+    pub fn open_with_comparator(
+        path: PathBuf,
+        comparator: Option<&Comparator>,
+        read_only: bool,
+    ) -> Result<Self, Error> {
+        if !path.is_dir() {
+            return Err(Error::Oops);
+        }
+        let options = TableOptions::read(&path)?;
+        check_comparator(&options, comparator)?;
+
+        let index_file_path = Self::index_file_path(&path);
+        let mut index_file = if read_only {
+            IndexFile::open_read_only(index_file_path)?
+        } else {
+            IndexFile::open(index_file_path)?
+        };
+        if let Some(comparator) = comparator {
+            index_file.set_comparator(comparator)?;
+        }
+
+        let mut table = Table::new(path, index_file, options);
+        table.read_only = read_only;
+        Ok(table)
+    }
+
+    /// Fail if the table is read-only, before anything on disk is touched.
+    pub(crate) fn check_writable(&self) -> Result<(), Error> {
+        if self.read_only {
+            return Err(Error::ReadOnly);
+        }
+        Ok(())
+    }
+
+    /// Read the value for `key`, or `None` if it is not present.
+    ///
+    /// For dupsort tables this is the first of the key's values.
+    pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        if self.options.dup_sort {
+            return Ok(self.get_all(key)?.into_iter().next());
+        }
+        self.read_value(key)
+    }
+
+    /// Read all values for `key` in sorted order, or an empty list if it is not present.
+    ///
+    /// Tables without dupsort have at most one value per key.
+    pub fn get_all(&self, key: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+        let Some(bytes) = self.read_value(key)? else {
+            return Ok(vec![]);
+        };
+        if self.options.dup_sort {
+            decode_dups(&bytes)
+        } else {
+            Ok(vec![bytes])
+        }
+    }
+
+    /// Write `value` for `key`.
+    ///
+    /// This replaces any existing value, except in dupsort tables where it adds `value` to the
+    /// key's set of values.
+    pub fn put(&self, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        self.check_writable()?;
+        self.options.check_put(key, value)?;
+        if self.options.dup_sort {
+            let mut values = self.get_all(key)?;
+            match values.binary_search_by(|v| v.as_slice().cmp(value)) {
+                Ok(_) => return Ok(()),
+                Err(i) => values.insert(i, value.to_vec()),
+            }
+            self.write_value(key, &encode_dups(&values))
+        } else {
+            self.write_value(key, value)
+        }
+    }
+
+    /// Remove `key` and all of its values, if present.
+    pub fn delete(&self, key: &[u8]) -> Result<(), Error> {
+        self.check_writable()?;
+        self.remove_value_file(key)?;
+        self.index_file.delete_key(key)
+    }
+
+    /// Remove a single `value` of `key`, deleting the key if it was the last one.
+    pub fn delete_dup(&self, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        self.check_writable()?;
+        let mut values = self.get_all(key)?;
+        let Ok(i) = values.binary_search_by(|v| v.as_slice().cmp(value)) else {
+            return Ok(());
+        };
+        values.remove(i);
+        if values.is_empty() {
+            self.delete(key)
+        } else {
+            self.write_value(key, &encode_dups(&values))
+        }
+    }
+
+    /// Rewrite the live values of segments in which more than `max_garbage_ratio` of the bytes
+    /// are garbage, then delete those segments.
     ///
-    /// Keys are encoded to ensure the path is filesystem safe.
-    pub fn key_path(&self, key: &[u8]) -> PathBuf {
-        let encoded_key = hex_string(key);
-        self.path.join(encoded_key)
+    /// Return the number of segments rewritten. Tables without packed storage have no segments.
+    pub fn compact(&self, max_garbage_ratio: f64) -> Result<usize, Error> {
+        self.check_writable()?;
+        let Some(segments) = &self.segments else {
+            return Ok(0);
+        };
+        let live_bytes = self
+            .index_file
+            .live_bytes_by_segment()?
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+
+        let mut to_compact = vec![];
+        for id in segments.list()? {
+            let len = segments.segment_len(id)?;
+            let live = live_bytes.get(&id).copied().unwrap_or(0);
+            if len == 0 || len.saturating_sub(live) as f64 / len as f64 > max_garbage_ratio {
+                to_compact.push(id);
+            }
+        }
+        if to_compact.is_empty() {
+            return Ok(0);
+        }
+
+        // Move live values into a fresh segment so none are appended to a segment being deleted.
+        segments.rotate()?;
+        for &id in &to_compact {
+            for (key, location) in self.index_file.keys_in_segment(id)? {
+                let new_location = segments.append(&segments.read(location)?)?;
+                self.index_file.put_key_location(&key, new_location)?;
+            }
+            segments.delete(id)?;
+        }
+        Ok(to_compact.len())
+    }
+
+    /// The table's name, from its directory name.
+    pub fn name(&self) -> String {
+        self.path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(decode_table_name)
+            .unwrap_or_else(|| self.path.display().to_string())
+    }
+
+    /// Read, verify and decode the value for `key`, or `None` if it's absent.
+    fn read_value(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        let bytes = match self.read_stored(key) {
+            Ok(Some(bytes)) => bytes,
+            Ok(None) => return Ok(None),
+            // A segment too short to hold a value it's recorded as holding has been truncated.
+            Err(Error::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                return Err(self.corruption(key));
+            }
+            Err(e) => return Err(e),
+        };
+        self.verify_checksum(key, &bytes)?;
+        self.options.compression.decompress(&bytes).map(Some)
+    }
+
+    /// Encode `value`, store it for `key` and record its checksum.
+    fn write_value(&self, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        let bytes = self.options.compression.compress(value)?;
+        self.write_stored(key, &bytes)?;
+        if let Some(checksum) = self.options.checksum.checksum(&bytes) {
+            self.index_file.set_checksum(key, Some(checksum))?;
+        }
+        Ok(())
+    }
+
+    /// Check the stored bytes of `key` against the checksum recorded when they were written.
+    fn verify_checksum(&self, key: &[u8], bytes: &[u8]) -> Result<(), Error> {
+        let Some(checksum) = self.options.checksum.checksum(bytes) else {
+            return Ok(());
+        };
+        // Every write records a checksum, so a missing one means the value or index is damaged.
+        if self.index_file.get_checksum(key)? != Some(checksum) {
+            return Err(self.corruption(key));
+        }
+        Ok(())
+    }
+
+    fn corruption(&self, key: &[u8]) -> Error {
+        Error::Corruption {
+            table: self.name(),
+            key: key.to_vec(),
+        }
+    }
+
+    /// Read the stored bytes for `key`, from the index or its file, or `None` if it's absent.
+    fn read_stored(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        if let Some(segments) = &self.segments {
+            return match self.index_file.get_location(key)? {
+                Some(location) => Ok(Some(segments.read(location)?)),
+                None => Ok(None),
+            };
+        }
+        if let ValueStorage::Inline { .. } = self.options.value_storage {
+            match self.index_file.get_value(key)? {
+                None => return Ok(None),
+                Some(Some(value)) => return Ok(Some(value)),
+                Some(None) => (),
+            }
+        }
+
+        let mut key_file = match File::open(self.key_path(key)?) {
+            Ok(f) => f,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut bytes = vec![];
+        key_file.read_to_end(&mut bytes)?;
+        Ok(Some(bytes))
+    }
+
+    /// Store `bytes` for `key` in the index or a file, and add `key` to the index.
+    fn write_stored(&self, key: &[u8], bytes: &[u8]) -> Result<(), Error> {
+        match self.options.value_storage {
+            ValueStorage::File => {
+                let file_name = self.write_value_file(key, bytes)?;
+                self.index_file.put_key(key)?;
+                self.record_file_name(key, Some(&file_name))
+            }
+            ValueStorage::Inline { .. } if self.options.value_storage.is_inline(bytes.len()) => {
+                // The previous value may have been large enough to need a file.
+                self.remove_value_file(key)?;
+                self.index_file.put_key_value(key, Some(bytes))
+            }
+            ValueStorage::Inline { .. } => {
+                let file_name = self.write_value_file(key, bytes)?;
+                self.index_file.put_key_value(key, None)?;
+                self.record_file_name(key, Some(&file_name))
+            }
+            ValueStorage::Packed { .. } => {
+                let segments = self.segments.as_ref().ok_or(Error::Oops)?;
+                let location = segments.append(bytes)?;
+                self.index_file.put_key_location(key, location)
+            }
+        }
+    }
+
+    /// Write the value file for `key`, returning its name.
+    fn write_value_file(&self, key: &[u8], bytes: &[u8]) -> Result<String, Error> {
+        let file_name = self.file_name(key)?;
+        let key_path = fan_out_path(&self.path, &file_name, self.options.fan_out);
+        if self.options.fan_out > 0 {
+            create_parent_dir(&key_path)?;
+        }
+        let mut key_file = File::create(key_path)?;
+        key_file.write_all(bytes)?;
+        Ok(file_name)
+    }
+
+    fn remove_value_file(&self, key: &[u8]) -> Result<(), Error> {
+        fs::remove_file(self.key_path(key)?).or_else(|e| {
+            if e.kind() == io::ErrorKind::NotFound {
+                Ok(())
+            } else {
+                Err(e)
+            }
+        })?;
+        // Release the name so that it can be used by another key with the same hash.
+        self.record_file_name(key, None)
+    }
+
+    /// Store the file name of `key` in the index, if the table uses hashed file names.
+    fn record_file_name(&self, key: &[u8], file_name: Option<&str>) -> Result<(), Error> {
+        if self.options.file_names == FileNameScheme::Hashed {
+            self.index_file.set_file_name(key, file_name)?;
+        }
+        Ok(())
+    }
+
+    /// Name of the file for `key`.
+    ///
+    /// With hashed file names this is the name recorded in the index, or the first candidate
+    /// name not already taken by another key.
+    fn file_name(&self, key: &[u8]) -> Result<String, Error> {
+        if self.options.file_names != FileNameScheme::Hashed {
+            return self.options.file_names.file_name(key);
+        }
+        if let Some(file_name) = self.index_file.get_file_name(key)? {
+            return Ok(file_name);
+        }
+        for attempt in 0.. {
+            let file_name = hashed_file_name(key, attempt);
+            if !fan_out_path(&self.path, &file_name, self.options.fan_out).exists() {
+                return Ok(file_name);
+            }
+        }
+        unreachable!()
+    }
+
+    /// Path to the file for a key, where it is or would be written.
+    ///
+    /// Keys are encoded by the table's `FileNameScheme` to ensure the path is filesystem safe.
+    pub fn key_path(&self, key: &[u8]) -> Result<PathBuf, Error> {
+        Ok(fan_out_path(
+            &self.path,
+            &self.file_name(key)?,
+            self.options.fan_out,
+        ))
+    }
+
+    /// Move all value files into the layout for `fan_out`, and record it in the table's options.
+    pub fn migrate_fan_out(&mut self, fan_out: u8) -> Result<(), Error> {
+        self.check_writable()?;
+        // Stage every file outside the layout first, because a value file in the old layout can
+        // have the same name as a directory in the new one.
+        let staging_path = self.path.join(FAN_OUT_STAGING_DIRNAME);
+        fs::create_dir_all(&staging_path)?;
+        let mut staged = vec![];
+        for key in self.index_file.keys()? {
+            let file_name = self.file_name(&key)?;
+            match fs::rename(self.key_path(&key)?, staging_path.join(&file_name)) {
+                Ok(()) => staged.push(file_name),
+                // Values stored in the index or segments have no file.
+                Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        remove_empty_fan_out_dirs(&self.path)?;
+
+        for file_name in staged {
+            let new_path = fan_out_path(&self.path, &file_name, fan_out);
+            create_parent_dir(&new_path)?;
+            fs::rename(staging_path.join(&file_name), new_path)?;
+        }
+        fs::remove_dir(staging_path)?;
+
+        self.options.fan_out = fan_out;
+        self.options.write(&self.path)
+    }
+
+    /// Check that every key in the index has a readable value and that every value file belongs
+    /// to a key.
+    pub fn verify(&self) -> Result<Vec<Inconsistency>, Error> {
+        let table = self.name();
+        let mut inconsistencies = vec![];
+        let mut key_paths = HashSet::new();
+        for key in self.index_file.keys()? {
+            match self.get_all(&key) {
+                Ok(values) if values.is_empty() => {
+                    inconsistencies.push(Inconsistency::MissingValue {
+                        table: table.clone(),
+                        key: key.clone(),
+                    });
+                }
+                Ok(_) => (),
+                // A missing segment leaves all the values in it missing.
+                Err(Error::Io(e)) if e.kind() == io::ErrorKind::NotFound => {
+                    inconsistencies.push(Inconsistency::MissingValue {
+                        table: table.clone(),
+                        key: key.clone(),
+                    });
+                }
+                Err(Error::Corruption { .. } | Error::Decode(_)) => {
+                    inconsistencies.push(Inconsistency::CorruptValue {
+                        table: table.clone(),
+                        key: key.clone(),
+                    });
+                }
+                Err(e) => return Err(e),
+            }
+            if self.has_value_file(&key)? {
+                key_paths.insert(self.key_path(&key)?);
+            }
+        }
+
+        for path in list_value_files(&self.path)? {
+            if key_paths.contains(&path) {
+                continue;
+            }
+            let file_name = path.file_name().and_then(|name| name.to_str());
+            let key = file_name.and_then(|name| self.options.file_names.decode(name));
+            if key.is_none() && self.options.file_names != FileNameScheme::Hashed {
+                inconsistencies.push(Inconsistency::UndecodableFileName {
+                    table: table.clone(),
+                    path,
+                });
+            } else {
+                inconsistencies.push(Inconsistency::OrphanValueFile {
+                    table: table.clone(),
+                    path,
+                    key,
+                });
+            }
+        }
+        Ok(inconsistencies)
+    }
+
+    /// Check whether the value of `key` is stored in a file, rather than the index or a segment.
+    fn has_value_file(&self, key: &[u8]) -> Result<bool, Error> {
+        match self.options.value_storage {
+            ValueStorage::File => Ok(true),
+            ValueStorage::Inline { .. } => Ok(self.index_file.get_value(key)? == Some(None)),
+            ValueStorage::Packed { .. } => Ok(false),
+        }
+    }
+
+    /// Fix `inconsistencies` found by `verify`.
+    ///
+    /// Keys with missing or corrupt values are deleted, as are orphaned and undecodable files.
+    pub fn repair(&self, inconsistencies: &[Inconsistency]) -> Result<(), Error> {
+        self.check_writable()?;
+        for inconsistency in inconsistencies {
+            match inconsistency {
+                Inconsistency::MissingValue { key, .. }
+                | Inconsistency::CorruptValue { key, .. } => self.delete(key)?,
+                Inconsistency::OrphanValueFile { path, .. }
+                | Inconsistency::UndecodableFileName { path, .. } => fs::remove_file(path)?,
+                Inconsistency::SkippedTable { .. } | Inconsistency::StrayGeneration(_) => (),
+            }
+        }
+        Ok(())
     }
 }
 
+/// Check that `comparator` is the one the table with `options` was created with.
+pub fn check_comparator(
+    options: &TableOptions,
+    comparator: Option<&Comparator>,
+) -> Result<(), Error> {
+    let stored = options.key_ordering.comparator_name();
+    let provided = comparator.map(|c| c.name);
+    if stored != provided {
+        return Err(Error::ComparatorMismatch {
+            stored: stored.map(str::to_string),
+            provided: provided.map(str::to_string),
+        });
+    }
+    Ok(())
+}
+
+/// Path to the file called `file_name` in the table at `table_path`, with `fan_out` levels of
+/// directories named by successive pairs of characters from `file_name`.
+///
+/// Names too short to name every level use `_` for the remaining directories.
+fn fan_out_path(table_path: &Path, file_name: &str, fan_out: u8) -> PathBuf {
+    let mut path = table_path.to_path_buf();
+    for level in 0..fan_out as usize {
+        path.push(file_name.get(2 * level..2 * level + 2).unwrap_or("_"));
+    }
+    path.push(file_name);
+    path
+}
+
+fn create_parent_dir(path: &Path) -> Result<(), Error> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(())
+}
+
+/// Remove fan-out directories under `dir` that no longer contain any value files.
+///
+/// Every visible directory in a table is part of the fan-out layout.
+fn remove_empty_fan_out_dirs(dir: &Path) -> Result<(), Error> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let is_fan_out_dir =
+            entry.file_type()?.is_dir() && !entry.file_name().as_encoded_bytes().starts_with(b".");
+        if !is_fan_out_dir {
+            continue;
+        }
+        remove_empty_fan_out_dirs(&entry.path())?;
+        match fs::remove_dir(entry.path()) {
+            Ok(()) => (),
+            Err(e) if e.kind() == io::ErrorKind::DirectoryNotEmpty => (),
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Encode the sorted values of a dupsort key as a sequence of length-prefixed values.
+fn encode_dups(values: &[Vec<u8>]) -> Vec<u8> {
+    let mut bytes = vec![];
+    for value in values {
+        bytes.extend_from_slice(&(value.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(value);
+    }
+    bytes
+}
+
+fn decode_dups(mut bytes: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+    let truncated = || Error::Decode("truncated dupsort value file".to_string());
+    let mut values = vec![];
+    while !bytes.is_empty() {
+        let (len, rest) = bytes.split_at_checked(4).ok_or_else(truncated)?;
+        let len = u32::from_be_bytes(len.try_into().unwrap()) as usize;
+        let (value, rest) = rest.split_at_checked(len).ok_or_else(truncated)?;
+        values.push(value.to_vec());
+        bytes = rest;
+    }
+    Ok(values)
+}
+
+/// Maximum length of an encoded table name, matching the usual filesystem limit.
+const MAX_ENCODED_NAME_LEN: usize = 255;
+
+/// Path to the directory for the table called `name` in the snapshot at `snapshot_path`.
+pub fn table_dir_path(snapshot_path: &Path, name: &str) -> Result<PathBuf, Error> {
+    Ok(snapshot_path.join(encode_table_name(name)?))
+}
+
+/// Encode a table name as a directory name.
+///
+/// Bytes other than ASCII alphanumerics, `-` and `_` are written as `%XX`. Encoded names never
+/// contain `.` or `/`, so they can't escape the snapshot or collide with internal files, which
+/// all have a `.` in their names.
+pub fn encode_table_name(name: &str) -> Result<String, Error> {
+    if name.is_empty() {
+        return Err(Error::InvalidTableName(name.to_string()));
+    }
+    let mut encoded = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    if encoded.len() > MAX_ENCODED_NAME_LEN {
+        return Err(Error::InvalidTableName(name.to_string()));
+    }
+    Ok(encoded)
+}
+
+/// Decode a directory name produced by `encode_table_name`.
+///
+/// Return `None` if `encoded` is not a valid encoding, e.g. because it is an internal file.
+pub fn decode_table_name(encoded: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut iter = encoded.bytes();
+    while let Some(byte) = iter.next() {
+        match byte {
+            b'%' => {
+                let hex = [iter.next()?, iter.next()?];
+                let hex = std::str::from_utf8(&hex).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            }
+            b if b.is_ascii_alphanumeric() || b == b'-' || b == b'_' => bytes.push(b),
+            _ => return None,
+        }
+    }
+    let name = String::from_utf8(bytes).ok()?;
+    // Reject non-canonical encodings so each table has exactly one directory name.
+    if encode_table_name(&name).ok()? != encoded {
+        return None;
+    }
+    Some(name)
+}
+
 impl TableId {
     pub fn new(id: usize) -> Self {
         Self { id }
     }
 }
+
+/// List the names of all tables in the snapshot at `snapshot_path`, in sorted order.
+///
+/// Entries that aren't encoded table names are skipped.
+pub fn list_tables(snapshot_path: &Path) -> Result<Vec<String>, Error> {
+    let mut names = vec![];
+    for entry in fs::read_dir(snapshot_path)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let Some(name) = entry.file_name().to_str().and_then(decode_table_name) else {
+            continue;
+        };
+        names.push(name);
+    }
+    names.sort();
+    Ok(names)
+}
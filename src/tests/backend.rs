@@ -0,0 +1,119 @@
+use super::test_root;
+use crate::{BackendKind, Database};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+/// Check that `path` and everything under it has the owner write bit set, or unset.
+fn assert_writable(path: &Path, writable: bool) {
+    let metadata = fs::metadata(path).unwrap();
+    assert_eq!(
+        metadata.permissions().mode() & 0o200 != 0,
+        writable,
+        "{}",
+        path.display()
+    );
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path).unwrap() {
+            assert_writable(&entry.unwrap().path(), writable);
+        }
+    }
+}
+
+#[test]
+fn backend_persisted() {
+    let root_path = test_root();
+    let db = Database::create_with_backend(root_path.path().to_path_buf(), BackendKind::Portable)
+        .unwrap();
+    drop(db);
+
+    assert_eq!(
+        BackendKind::read(root_path.path()).unwrap(),
+        BackendKind::Portable
+    );
+    Database::open_or_create(root_path.path().to_path_buf()).unwrap();
+}
+
+#[test]
+fn portable_commit_checkpoint_restore() {
+    let root_path = test_root();
+    let db = Database::create_with_backend(root_path.path().to_path_buf(), BackendKind::Portable)
+        .unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.create_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    txn.put(t, &[0], &[0]).unwrap();
+    txn.commit().unwrap();
+
+    db.checkpoint("c").unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.open_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    txn.put(t, &[0], &[1]).unwrap();
+    txn.commit().unwrap();
+
+    let mut read = db.begin_read().unwrap();
+    let tid = read.open_table("t").unwrap();
+    let t = read.get_table(tid).unwrap();
+    assert_eq!(read.get(t, &[0]).unwrap(), Some(vec![1]));
+    drop(read);
+
+    let mut read = db.begin_read_checkpoint("c").unwrap();
+    let tid = read.open_table("t").unwrap();
+    let t = read.get_table(tid).unwrap();
+    assert_eq!(read.get(t, &[0]).unwrap(), Some(vec![0]));
+    drop(read);
+
+    db.restore_checkpoint("c").unwrap();
+    let mut read = db.begin_read().unwrap();
+    let tid = read.open_table("t").unwrap();
+    let t = read.get_table(tid).unwrap();
+    assert_eq!(read.get(t, &[0]).unwrap(), Some(vec![0]));
+    drop(read);
+
+    db.delete_checkpoint("c").unwrap();
+    assert!(db.list_checkpoints().unwrap().is_empty());
+
+    // Only the latest generation remains once obsolete ones are reclaimed.
+    db.wait_for_reclaim();
+    let generations = std::fs::read_dir(root_path.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name.starts_with("gen-"))
+        .count();
+    assert_eq!(generations, 1);
+}
+
+#[test]
+fn portable_restored_checkpoint_is_writable() {
+    let root_path = test_root();
+    let db = Database::create_with_backend(root_path.path().to_path_buf(), BackendKind::Portable)
+        .unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.create_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    txn.put(t, &[0], &[0]).unwrap();
+    txn.commit().unwrap();
+
+    db.checkpoint("c").unwrap();
+    let mut read = db.begin_read_checkpoint("c").unwrap();
+    let tid = read.open_table("t").unwrap();
+    assert_writable(&read.get_table(tid).unwrap().path, false);
+    drop(read);
+
+    db.restore_checkpoint("c").unwrap();
+    let mut read = db.begin_read().unwrap();
+    let tid = read.open_table("t").unwrap();
+    let table_path = read.get_table(tid).unwrap().path.clone();
+    drop(read);
+    assert_writable(table_path.parent().unwrap(), true);
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.open_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    txn.put(t, &[0], &[1]).unwrap();
+    txn.commit().unwrap();
+}
@@ -0,0 +1,149 @@
+use super::test_root;
+use crate::{Database, Error};
+
+#[test]
+fn checkpoint_time_travel() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.create_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    txn.put(t, &[0], &[0]).unwrap();
+    txn.commit().unwrap();
+
+    db.checkpoint("before-migration").unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.open_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    txn.put(t, &[0], &[1]).unwrap();
+    txn.put(t, &[1], &[1]).unwrap();
+    txn.commit().unwrap();
+
+    assert_eq!(db.list_checkpoints().unwrap(), vec!["before-migration"]);
+
+    // The checkpoint still contains the data from before the second commit.
+    let mut read = db.begin_read_checkpoint("before-migration").unwrap();
+    assert_eq!(read.checkpoint_name(), Some("before-migration"));
+    assert_eq!(read.generation(), None);
+    let tid = read.open_table("t").unwrap();
+    let t = read.get_table(tid).unwrap();
+    assert_eq!(read.get(t, &[0]).unwrap(), Some(vec![0]));
+
+    let mut cursor = read.cursor(t).unwrap();
+    assert_eq!(&*cursor.first_key().unwrap().unwrap(), &[0]);
+    assert_eq!(cursor.next_key().unwrap(), None);
+    drop(cursor);
+    drop(read);
+
+    db.delete_checkpoint("before-migration").unwrap();
+    assert!(db.list_checkpoints().unwrap().is_empty());
+}
+
+#[test]
+fn checkpoint_errors() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    assert!(db.list_checkpoints().unwrap().is_empty());
+    assert!(matches!(
+        db.checkpoint("../escape"),
+        Err(Error::InvalidCheckpointName(_))
+    ));
+    assert!(matches!(
+        db.begin_read_checkpoint("missing"),
+        Err(Error::CheckpointNotFound(_))
+    ));
+    assert!(matches!(
+        db.delete_checkpoint("missing"),
+        Err(Error::CheckpointNotFound(_))
+    ));
+
+    db.checkpoint("c").unwrap();
+    assert!(matches!(
+        db.checkpoint("c"),
+        Err(Error::CheckpointExists(_))
+    ));
+}
+
+#[test]
+fn readers_pin_checkpoints() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.create_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    txn.put(t, &[0], &[0]).unwrap();
+    txn.commit().unwrap();
+    db.checkpoint("c").unwrap();
+
+    let mut read = db.begin_read_checkpoint("c").unwrap();
+    let other = db.begin_read_checkpoint("c").unwrap();
+    assert!(matches!(
+        db.delete_checkpoint("c"),
+        Err(Error::CheckpointInUse(_))
+    ));
+    drop(other);
+    assert!(matches!(
+        db.delete_checkpoint("c"),
+        Err(Error::CheckpointInUse(_))
+    ));
+
+    let tid = read.open_table("t").unwrap();
+    let t = read.get_table(tid).unwrap();
+    assert_eq!(read.get(t, &[0]).unwrap(), Some(vec![0]));
+    drop(read);
+
+    db.delete_checkpoint("c").unwrap();
+    assert!(db.list_checkpoints().unwrap().is_empty());
+}
+
+#[test]
+fn restore_checkpoint() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.create_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    txn.put(t, &[0], &[0]).unwrap();
+    txn.commit().unwrap();
+
+    db.checkpoint("good").unwrap();
+
+    // Make a bad commit.
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.open_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    txn.put(t, &[0], &[0xba, 0xd]).unwrap();
+    txn.create_table("junk").unwrap();
+    txn.commit().unwrap();
+
+    db.restore_checkpoint("good").unwrap();
+
+    let mut read = db.begin_read().unwrap();
+    assert_eq!(read.list_tables().unwrap(), vec!["t"]);
+    let tid = read.open_table("t").unwrap();
+    let t = read.get_table(tid).unwrap();
+    assert_eq!(read.get(t, &[0]).unwrap(), Some(vec![0]));
+    drop(read);
+
+    // The restored generation is writable and the checkpoint is unchanged.
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.open_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    txn.put(t, &[1], &[1]).unwrap();
+    txn.commit().unwrap();
+    assert_eq!(db.list_checkpoints().unwrap(), vec!["good"]);
+
+    // The restore survives reopening the database.
+    drop(db);
+    let db = Database::open_or_create(root_path.path().to_path_buf()).unwrap();
+    let mut read = db.begin_read().unwrap();
+    let tid = read.open_table("t").unwrap();
+    let t = read.get_table(tid).unwrap();
+    assert_eq!(read.get(t, &[0]).unwrap(), Some(vec![0]));
+    assert_eq!(read.get(t, &[1]).unwrap(), Some(vec![1]));
+}
@@ -0,0 +1,106 @@
+use super::test_root;
+use crate::segment::list_segments;
+use crate::{ChecksumMode, Database, Error, TableOptions, ValueStorage};
+use std::fs::{self, OpenOptions};
+
+fn is_corruption(result: Result<impl Sized, Error>, table: &str, key: &[u8]) -> bool {
+    matches!(
+        result,
+        Err(Error::Corruption { table: t, key: k }) if t == table && k == key
+    )
+}
+
+#[test]
+fn corrupt_value_file() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let options = TableOptions {
+        checksum: ChecksumMode::Crc32c,
+        ..TableOptions::default()
+    };
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.create_table_with("t", options).unwrap();
+    let t = txn.get_table(tid).unwrap();
+    txn.put(t, b"a", b"hello").unwrap();
+    txn.put(t, b"b", b"world").unwrap();
+    assert_eq!(txn.get(t, b"a").unwrap(), Some(b"hello".to_vec()));
+
+    // Flip a byte in one value and truncate the other.
+    fs::write(t.key_path(b"a").unwrap(), b"jello").unwrap();
+    OpenOptions::new()
+        .write(true)
+        .open(t.key_path(b"b").unwrap())
+        .unwrap()
+        .set_len(2)
+        .unwrap();
+    assert!(is_corruption(txn.get(t, b"a"), "t", b"a"));
+    assert!(is_corruption(txn.get(t, b"b"), "t", b"b"));
+
+    let mut cursor = txn.cursor(t).unwrap();
+    cursor.first_key().unwrap();
+    assert!(is_corruption(cursor.get_current(), "t", b"a"));
+
+    // Rewriting a value records a new checksum.
+    txn.put(t, b"a", b"fixed").unwrap();
+    assert_eq!(txn.get(t, b"a").unwrap(), Some(b"fixed".to_vec()));
+}
+
+#[test]
+fn truncated_segment() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let options = TableOptions {
+        checksum: ChecksumMode::Crc32c,
+        value_storage: ValueStorage::Packed { segment_size: 1024 },
+        ..TableOptions::default()
+    };
+    let tid = txn.create_table_with("t", options).unwrap();
+    let t = txn.get_table(tid).unwrap();
+    txn.put(t, b"a", &[1; 16]).unwrap();
+    txn.put(t, b"b", &[2; 16]).unwrap();
+    txn.commit().unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.open_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    let segment = t.path.join(format!(
+        "segment-{}.dat",
+        list_segments(&t.path).unwrap()[0]
+    ));
+    OpenOptions::new()
+        .write(true)
+        .open(segment)
+        .unwrap()
+        .set_len(24)
+        .unwrap();
+    assert_eq!(txn.get(t, b"a").unwrap(), Some(vec![1; 16]));
+    assert!(is_corruption(txn.get(t, b"b"), "t", b"b"));
+}
+
+#[test]
+fn checksums_survive_reopen() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let options = TableOptions {
+        checksum: ChecksumMode::Crc32c,
+        value_storage: ValueStorage::Inline { threshold: 8 },
+        ..TableOptions::default()
+    };
+    let tid = txn.create_table_with("t", options).unwrap();
+    let t = txn.get_table(tid).unwrap();
+    txn.put(t, b"small", b"x").unwrap();
+    txn.put(t, b"large", &[7; 64]).unwrap();
+    txn.commit().unwrap();
+
+    let mut txn = db.begin_read().unwrap();
+    let tid = txn.open_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    assert_eq!(t.options.checksum, ChecksumMode::Crc32c);
+    assert_eq!(txn.get(t, b"small").unwrap(), Some(b"x".to_vec()));
+    assert_eq!(txn.get(t, b"large").unwrap(), Some(vec![7; 64]));
+}
@@ -0,0 +1,126 @@
+use super::test_root;
+use crate::{Comparator, Database, Error, TableOptions};
+use std::cmp::Ordering;
+
+fn compare_le_u64(a: &[u8], b: &[u8]) -> Ordering {
+    let decode = |bytes: &[u8]| {
+        let mut buf = [0; 8];
+        buf[..bytes.len().min(8)].copy_from_slice(&bytes[..bytes.len().min(8)]);
+        u64::from_le_bytes(buf)
+    };
+    decode(a).cmp(&decode(b)).then_with(|| a.cmp(b))
+}
+
+const LE_U64: Comparator = Comparator {
+    name: "le_u64",
+    compare: compare_le_u64,
+};
+
+const OTHER: Comparator = Comparator {
+    name: "other",
+    compare: <[u8]>::cmp,
+};
+
+#[test]
+fn little_endian_order() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let keys = [256u64, 1, 65536, 255, 0];
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn
+        .create_table_with_comparator("t", TableOptions::default(), &LE_U64)
+        .unwrap();
+    let t = txn.get_table(tid).unwrap();
+    for k in keys {
+        txn.put(t, &k.to_le_bytes(), &[]).unwrap();
+    }
+    txn.commit().unwrap();
+
+    let mut sorted = keys.to_vec();
+    sorted.sort();
+
+    let mut read = db.begin_read().unwrap();
+    let tid = read.open_table_with_comparator("t", &LE_U64).unwrap();
+    let t = read.get_table(tid).unwrap();
+    let mut cursor = read.cursor(t).unwrap();
+    let mut seen = vec![];
+    while let Some(key) = cursor.next_key().unwrap() {
+        seen.push(u64::from_le_bytes(key.as_ref().try_into().unwrap()));
+    }
+    assert_eq!(seen, sorted);
+
+    let last = cursor.last_key().unwrap().unwrap();
+    assert_eq!(last.as_ref(), 65536u64.to_le_bytes());
+}
+
+#[test]
+fn case_insensitive_order() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let case_insensitive = Comparator {
+        name: "case_insensitive",
+        compare: |a, b| a.to_ascii_lowercase().cmp(&b.to_ascii_lowercase()),
+    };
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn
+        .create_table_with_comparator("t", TableOptions::default(), &case_insensitive)
+        .unwrap();
+    let t = txn.get_table(tid).unwrap();
+    for key in [b"b", b"A", b"a", b"B"] {
+        txn.put(t, key, key).unwrap();
+    }
+    txn.commit().unwrap();
+
+    // Keys that only differ in case are distinct, and ordered by their bytes.
+    let mut read = db.begin_read().unwrap();
+    let tid = read
+        .open_table_with_comparator("t", &case_insensitive)
+        .unwrap();
+    let t = read.get_table(tid).unwrap();
+    assert_eq!(read.get(t, b"a").unwrap(), Some(b"a".to_vec()));
+    assert_eq!(read.get(t, b"A").unwrap(), Some(b"A".to_vec()));
+    let mut cursor = read.cursor(t).unwrap();
+    let mut seen = vec![];
+    while let Some(key) = cursor.next_key().unwrap() {
+        seen.push(key.to_vec());
+    }
+    assert_eq!(seen, [b"A", b"a", b"B", b"b"]);
+}
+
+#[test]
+fn wrong_comparator() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    txn.create_table_with_comparator("custom", TableOptions::default(), &LE_U64)
+        .unwrap();
+    txn.create_table("plain").unwrap();
+
+    assert!(matches!(
+        txn.open_table("custom"),
+        Err(Error::ComparatorMismatch { .. })
+    ));
+    assert!(matches!(
+        txn.open_table_with_comparator("custom", &OTHER),
+        Err(Error::ComparatorMismatch { .. })
+    ));
+    assert!(matches!(
+        txn.open_table_with_comparator("plain", &LE_U64),
+        Err(Error::ComparatorMismatch { .. })
+    ));
+
+    let bad_name = Comparator {
+        name: "bad name",
+        compare: <[u8]>::cmp,
+    };
+    assert!(matches!(
+        txn.create_table_with_comparator("bad", TableOptions::default(), &bad_name),
+        Err(Error::InvalidComparatorName(_))
+    ));
+    assert!(!txn.table_exists("bad"));
+}
@@ -0,0 +1,104 @@
+use super::test_root;
+use crate::backend::BackendKind;
+#[cfg(any(feature = "zstd", feature = "lz4"))]
+use crate::ValueStorage;
+use crate::{BtrfsCompression, Compression, Database, Error, TableOptions};
+use std::fs;
+
+#[cfg(any(feature = "zstd", feature = "lz4"))]
+fn round_trip(compression: Compression) {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    for (name, value_storage) in [
+        ("file", ValueStorage::File),
+        ("inline", ValueStorage::Inline { threshold: 1024 }),
+        ("packed", ValueStorage::Packed { segment_size: 4096 }),
+    ] {
+        let options = TableOptions {
+            compression,
+            value_storage,
+            ..TableOptions::default()
+        };
+        let tid = txn.create_table_with(name, options).unwrap();
+        let t = txn.get_table(tid).unwrap();
+        txn.put(t, b"big", &[7; 512]).unwrap();
+        txn.put(t, b"small", b"x").unwrap();
+        txn.put(t, b"empty", b"").unwrap();
+        if value_storage == ValueStorage::File {
+            assert!(fs::metadata(t.key_path(b"big").unwrap()).unwrap().len() < 512);
+        }
+    }
+    txn.commit().unwrap();
+
+    let mut txn = db.begin_read().unwrap();
+    for name in ["file", "inline", "packed"] {
+        let tid = txn.open_table(name).unwrap();
+        let t = txn.get_table(tid).unwrap();
+        assert_eq!(txn.get(t, b"big").unwrap(), Some(vec![7; 512]));
+        assert_eq!(txn.get(t, b"small").unwrap(), Some(b"x".to_vec()));
+        assert_eq!(txn.get(t, b"empty").unwrap(), Some(vec![]));
+        assert_eq!(txn.get(t, b"missing").unwrap(), None);
+    }
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn zstd_round_trip() {
+    round_trip(Compression::Zstd { level: 3 });
+}
+
+#[cfg(feature = "lz4")]
+#[test]
+fn lz4_round_trip() {
+    round_trip(Compression::Lz4);
+}
+
+#[test]
+fn unsupported_compression() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    for compression in [Compression::Zstd { level: 3 }, Compression::Lz4] {
+        if compression.check_supported().is_ok() {
+            continue;
+        }
+        let options = TableOptions {
+            compression,
+            ..TableOptions::default()
+        };
+        assert!(matches!(
+            txn.create_table_with("t", options),
+            Err(Error::UnsupportedCompression(_))
+        ));
+        assert!(!txn.table_exists("t"));
+    }
+}
+
+#[test]
+fn btrfs_compression() {
+    let root_path = test_root();
+    if BackendKind::detect(root_path.path()).unwrap() != BackendKind::Btrfs {
+        return;
+    }
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let options = TableOptions {
+        compression: Compression::Btrfs(BtrfsCompression::Zstd),
+        ..TableOptions::default()
+    };
+    let tid = txn.create_table_with("t", options).unwrap();
+    let t = txn.get_table(tid).unwrap();
+    txn.put(t, b"key", &[7; 512]).unwrap();
+    // btrfs compresses transparently, so the file holds the value as-is.
+    assert_eq!(fs::read(t.key_path(b"key").unwrap()).unwrap(), vec![7; 512]);
+    txn.commit().unwrap();
+
+    let mut txn = db.begin_read().unwrap();
+    let tid = txn.open_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    assert_eq!(txn.get(t, b"key").unwrap(), Some(vec![7; 512]));
+}
@@ -0,0 +1,75 @@
+use super::test_root;
+use crate::{Database, TableOptions};
+
+#[test]
+fn put_get_all() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let options = TableOptions {
+        dup_sort: true,
+        ..TableOptions::default()
+    };
+    let tid = txn.create_table_with("t", options).unwrap();
+    let t = txn.get_table(tid).unwrap();
+
+    txn.put(t, b"k", b"c").unwrap();
+    txn.put(t, b"k", b"a").unwrap();
+    txn.put(t, b"k", b"b").unwrap();
+    txn.put(t, b"k", b"a").unwrap();
+    txn.put(t, b"k", b"").unwrap();
+    txn.commit().unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.open_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    let all = txn.get_all(t, b"k").unwrap();
+    assert_eq!(
+        all,
+        vec![b"".to_vec(), b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]
+    );
+    assert_eq!(txn.get(t, b"k").unwrap(), Some(vec![]));
+    assert!(txn.get_all(t, b"missing").unwrap().is_empty());
+
+    txn.delete_dup(t, b"k", b"b").unwrap();
+    assert_eq!(txn.get_all(t, b"k").unwrap().len(), 3);
+    for v in [&b""[..], b"a", b"c"] {
+        txn.delete_dup(t, b"k", v).unwrap();
+    }
+    assert_eq!(txn.get(t, b"k").unwrap(), None);
+    let mut cursor = txn.cursor(t).unwrap();
+    assert_eq!(cursor.first_key().unwrap(), None);
+}
+
+#[test]
+fn cursor_dups() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let options = TableOptions {
+        dup_sort: true,
+        ..TableOptions::default()
+    };
+    let tid = txn.create_table_with("t", options).unwrap();
+    let t = txn.get_table(tid).unwrap();
+    for (k, v) in [(0, 2), (0, 1), (1, 5), (2, 9), (2, 8), (2, 7)] {
+        txn.put(t, &[k], &[v]).unwrap();
+    }
+
+    // Walk every value of every key.
+    let mut cursor = txn.cursor(t).unwrap();
+    let mut seen = vec![];
+    while let Some((k, v)) = cursor.get_current().unwrap() {
+        seen.push((k[0], v[0]));
+        if cursor.next_dup().unwrap().is_none() && cursor.next_nodup().unwrap().is_none() {
+            break;
+        }
+    }
+    assert_eq!(seen, vec![(0, 1), (0, 2), (1, 5), (2, 7), (2, 8), (2, 9)]);
+
+    // Rewind to the first value of the last key.
+    assert_eq!(cursor.first_dup().unwrap().as_deref(), Some(&[7][..]));
+    assert_eq!(cursor.next_dup().unwrap().as_deref(), Some(&[8][..]));
+}
@@ -0,0 +1,82 @@
+use super::test_root;
+use crate::{Database, TableOptions};
+
+#[test]
+fn fan_out_layout() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let options = TableOptions {
+        fan_out: 2,
+        ..TableOptions::default()
+    };
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.create_table_with("t", options).unwrap();
+    let t = txn.get_table(tid).unwrap();
+
+    txn.put(t, &[0xab, 0xcd, 0xef], &[1]).unwrap();
+    txn.put(t, &[0xab], &[2]).unwrap();
+    assert_eq!(
+        t.key_path(&[0xab, 0xcd, 0xef]).unwrap(),
+        t.path.join("ab/cd/abcdef")
+    );
+    assert!(t.path.join("ab/cd/abcdef").is_file());
+    assert!(t.path.join("ab/_/ab").is_file());
+    txn.commit().unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.open_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    assert_eq!(txn.get(t, &[0xab, 0xcd, 0xef]).unwrap(), Some(vec![1]));
+
+    let mut cursor = txn.cursor(t).unwrap();
+    assert_eq!(cursor.get_current().unwrap().unwrap().1.as_ref(), &[2][..]);
+    cursor.delete_current().unwrap();
+    assert!(!t.path.join("ab/_/ab").exists());
+    drop(cursor);
+
+    txn.delete(t, &[0xab, 0xcd, 0xef]).unwrap();
+    assert_eq!(txn.get(t, &[0xab, 0xcd, 0xef]).unwrap(), None);
+}
+
+#[test]
+fn migrate_flat_table() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let keys: Vec<Vec<u8>> = vec![vec![0xab], vec![0xab, 0xcd], vec![0x01, 0x02, 0x03]];
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.create_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    for key in &keys {
+        txn.put(t, key, key).unwrap();
+    }
+    txn.commit().unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    txn.migrate_fan_out("t", 1).unwrap();
+    txn.commit().unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.open_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    assert_eq!(t.options.fan_out, 1);
+    assert!(t.path.join("ab/ab").is_file());
+    assert!(t.path.join("ab/abcd").is_file());
+    assert!(t.path.join("01/010203").is_file());
+    for key in &keys {
+        assert_eq!(txn.get(t, key).unwrap().as_ref(), Some(key));
+    }
+    drop(txn);
+
+    // And back again.
+    let mut txn = db.begin_transaction().unwrap();
+    txn.migrate_fan_out("t", 0).unwrap();
+    let tid = txn.open_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    assert!(t.path.join("ab").is_file());
+    assert!(!t.path.join("01").exists());
+    for key in &keys {
+        assert_eq!(txn.get(t, key).unwrap().as_ref(), Some(key));
+    }
+}
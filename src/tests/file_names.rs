@@ -0,0 +1,107 @@
+use super::test_root;
+use crate::file_name::hashed_file_name;
+use crate::{Database, Error, FileNameScheme, TableOptions};
+use std::fs;
+
+#[test]
+fn long_keys() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let hex = txn.create_table("hex").unwrap();
+    let base64 = TableOptions {
+        file_names: FileNameScheme::Base64Url,
+        ..TableOptions::default()
+    };
+    let base64 = txn.create_table_with("base64", base64).unwrap();
+    let hashed = TableOptions {
+        file_names: FileNameScheme::Hashed,
+        ..TableOptions::default()
+    };
+    let hashed = txn.create_table_with("hashed", hashed).unwrap();
+
+    let key = vec![7; 150];
+    let t = txn.get_table(hex).unwrap();
+    assert!(matches!(
+        txn.put(t, &key, &[1]),
+        Err(Error::KeyTooLarge { len: 150, max: 127 })
+    ));
+
+    let t = txn.get_table(base64).unwrap();
+    txn.put(t, &key, &[2]).unwrap();
+    assert_eq!(txn.get(t, &key).unwrap(), Some(vec![2]));
+
+    let long_key = vec![9; 4096];
+    let t = txn.get_table(hashed).unwrap();
+    txn.put(t, &long_key, &[3]).unwrap();
+    txn.commit().unwrap();
+
+    let mut read = db.begin_read().unwrap();
+    let tid = read.open_table("hashed").unwrap();
+    let t = read.get_table(tid).unwrap();
+    assert_eq!(read.get(t, &long_key).unwrap(), Some(vec![3]));
+    assert_eq!(read.get(t, &[9; 4095]).unwrap(), None);
+}
+
+#[test]
+fn hash_collisions() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let options = TableOptions {
+        file_names: FileNameScheme::Hashed,
+        ..TableOptions::default()
+    };
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.create_table_with("t", options).unwrap();
+    let t = txn.get_table(tid).unwrap();
+
+    // Occupy the first candidate name for `b`, as a key with a colliding hash would.
+    fs::write(t.path.join(hashed_file_name(b"b", 0)), b"other").unwrap();
+
+    txn.put(t, b"a", b"1").unwrap();
+    txn.put(t, b"b", b"2").unwrap();
+    assert_eq!(
+        t.key_path(b"b").unwrap(),
+        t.path.join(hashed_file_name(b"b", 1))
+    );
+    assert_eq!(txn.get(t, b"a").unwrap(), Some(b"1".to_vec()));
+    assert_eq!(txn.get(t, b"b").unwrap(), Some(b"2".to_vec()));
+
+    // Overwriting keeps the same file, and deleting frees it.
+    txn.put(t, b"b", b"3").unwrap();
+    assert_eq!(
+        fs::read(t.path.join(hashed_file_name(b"b", 1))).unwrap(),
+        b"3"
+    );
+    txn.delete(t, b"b").unwrap();
+    assert!(!t.path.join(hashed_file_name(b"b", 1)).exists());
+    assert_eq!(txn.get(t, b"b").unwrap(), None);
+}
+
+#[test]
+fn value_files_never_replace_internal_files() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    // This key is named `options` in base64url, which was once the name of the options file.
+    let key = b"\xa2\x9b\x62\xa2\x7b";
+    let options = TableOptions {
+        file_names: FileNameScheme::Base64Url,
+        ..TableOptions::default()
+    };
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.create_table_with("t", options.clone()).unwrap();
+    let t = txn.get_table(tid).unwrap();
+    txn.put(t, key, b"garbage").unwrap();
+    txn.commit().unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.open_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    assert_eq!(t.options, options);
+    assert_eq!(txn.get(t, key).unwrap(), Some(b"garbage".to_vec()));
+    drop(txn);
+    assert!(db.verify().unwrap().is_clean());
+}
@@ -0,0 +1,77 @@
+use super::test_root;
+use crate::{Database, TableOptions, ValueStorage};
+
+#[test]
+fn small_values_are_inline() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let options = TableOptions {
+        value_storage: ValueStorage::Inline { threshold: 8 },
+        ..TableOptions::default()
+    };
+    let tid = txn.create_table_with("t", options).unwrap();
+    let t = txn.get_table(tid).unwrap();
+
+    txn.put(t, &[0], &[1; 8]).unwrap();
+    txn.put(t, &[1], &[2; 9]).unwrap();
+    txn.put(t, &[2], &[]).unwrap();
+    assert!(!t.key_path(&[0]).unwrap().exists());
+    assert!(t.key_path(&[1]).unwrap().exists());
+    assert!(!t.key_path(&[2]).unwrap().exists());
+    txn.commit().unwrap();
+
+    let mut read = db.begin_read().unwrap();
+    let tid = read.open_table("t").unwrap();
+    let t = read.get_table(tid).unwrap();
+    assert_eq!(read.get(t, &[0]).unwrap(), Some(vec![1; 8]));
+    assert_eq!(read.get(t, &[1]).unwrap(), Some(vec![2; 9]));
+    assert_eq!(read.get(t, &[2]).unwrap(), Some(vec![]));
+    assert_eq!(read.get(t, &[3]).unwrap(), None);
+
+    let mut cursor = read.cursor(t).unwrap();
+    let mut seen = vec![];
+    while let Some((k, v)) = cursor.get_current().unwrap() {
+        seen.push((k.to_vec(), v.to_vec()));
+        if cursor.next_key().unwrap().is_none() {
+            break;
+        }
+    }
+    assert_eq!(
+        seen,
+        vec![
+            (vec![0], vec![1; 8]),
+            (vec![1], vec![2; 9]),
+            (vec![2], vec![])
+        ]
+    );
+}
+
+#[test]
+fn overwrite_moves_between_storage() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let options = TableOptions {
+        value_storage: ValueStorage::Inline { threshold: 8 },
+        ..TableOptions::default()
+    };
+    let tid = txn.create_table_with("t", options).unwrap();
+    let t = txn.get_table(tid).unwrap();
+
+    txn.put(t, &[0], &[1; 100]).unwrap();
+    assert!(t.key_path(&[0]).unwrap().exists());
+
+    txn.put(t, &[0], &[2]).unwrap();
+    assert!(!t.key_path(&[0]).unwrap().exists());
+    assert_eq!(txn.get(t, &[0]).unwrap(), Some(vec![2]));
+
+    txn.put(t, &[0], &[3; 100]).unwrap();
+    assert_eq!(txn.get(t, &[0]).unwrap(), Some(vec![3; 100]));
+
+    txn.delete(t, &[0]).unwrap();
+    assert!(!t.key_path(&[0]).unwrap().exists());
+    assert_eq!(txn.get(t, &[0]).unwrap(), None);
+}
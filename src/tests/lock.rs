@@ -0,0 +1,22 @@
+use super::test_root;
+use crate::{Database, Error};
+
+#[test]
+fn second_open_fails_fast() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let pid = std::process::id();
+    assert!(matches!(
+        Database::try_open(root_path.path().to_path_buf()),
+        Err(Error::DatabaseLocked { pid: Some(p) }) if p == pid
+    ));
+    assert!(matches!(
+        Database::create(root_path.path().to_path_buf()),
+        Err(Error::DatabaseLocked { .. })
+    ));
+
+    // The lock is released when the database is dropped.
+    drop(db);
+    Database::try_open(root_path.path().to_path_buf()).unwrap();
+}
@@ -1,15 +1,36 @@
 #![cfg(test)]
+mod backend;
 mod basic;
+mod checkpoint;
+mod checksum;
+mod comparator;
+mod compression;
 mod cursor;
+mod dupsort;
+mod fan_out;
+mod file_names;
+mod inline;
+mod lock;
+mod options;
+mod read_transaction;
+mod reader;
+mod reclaim;
+mod recovery;
+mod segment;
+mod table;
+mod typed;
+mod verify;
 
 use std::path::PathBuf;
 use tempfile::{tempdir_in, TempDir};
 
-/// User nominated test directory.
+/// User nominated test directory, set by `BUTTER_DB_TEST_DIR` or defaulting to `/mnt/database/`.
 ///
-/// This *must* be a BTRFS volume.
+/// Any filesystem will do, but a BTRFS volume is required to test the btrfs backend.
 pub fn test_dir() -> PathBuf {
-    PathBuf::from("/mnt/database/")
+    std::env::var_os("BUTTER_DB_TEST_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/mnt/database/"))
 }
 
 /// New temporary directory in `test_dir`.
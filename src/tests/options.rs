@@ -0,0 +1,57 @@
+use super::test_root;
+use crate::{Database, Error, TableOptions};
+
+#[test]
+fn options_persist() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+    let options = TableOptions {
+        max_key_size: Some(4),
+        max_value_size: Some(8),
+        ..TableOptions::default()
+    };
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.create_table_with("t", options.clone()).unwrap();
+    let t = txn.get_table(tid).unwrap();
+    txn.put(t, &[0; 4], &[0; 8]).unwrap();
+    txn.commit().unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.open_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    assert_eq!(t.options, options);
+    assert!(matches!(
+        txn.put(t, &[0; 5], &[]),
+        Err(Error::KeyTooLarge { len: 5, max: 4 })
+    ));
+    assert!(matches!(
+        txn.put(t, &[0], &[0; 9]),
+        Err(Error::ValueTooLarge { len: 9, max: 8 })
+    ));
+    drop(txn);
+
+    let mut read = db.begin_read().unwrap();
+    let tid = read.open_table("t").unwrap();
+    assert_eq!(read.get_table(tid).unwrap().options, options);
+}
+
+#[test]
+fn options_file_format() {
+    let options = TableOptions {
+        max_value_size: Some(100),
+        ..TableOptions::default()
+    };
+    assert_eq!(
+        options.to_string().parse::<TableOptions>().unwrap(),
+        options
+    );
+
+    // Missing entries take their defaults, but unknown entries are rejected.
+    let parsed: TableOptions = "max_key_size = 3\n".parse().unwrap();
+    assert_eq!(parsed.max_key_size, Some(3));
+    assert_eq!(parsed.max_value_size, None);
+    assert!("max_key_size = 3\nbogus = 1\n"
+        .parse::<TableOptions>()
+        .is_err());
+}
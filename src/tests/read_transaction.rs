@@ -0,0 +1,126 @@
+use super::test_root;
+use crate::{Database, Error, Generation};
+
+#[test]
+fn read_committed_data() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.create_table("b").unwrap();
+    txn.create_table("a").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    txn.put(t, &[1], &[11]).unwrap();
+    txn.put(t, &[0], &[0]).unwrap();
+    txn.commit().unwrap();
+
+    let mut read = db.begin_read().unwrap();
+    assert_eq!(read.list_tables().unwrap(), vec!["a", "b"]);
+
+    let tid = read.open_table("b").unwrap();
+    let t = read.get_table(tid).unwrap();
+    assert_eq!(read.get(t, &[1]).unwrap(), Some(vec![11]));
+    assert_eq!(read.get(t, &[2]).unwrap(), None);
+
+    let mut cursor = read.cursor(t).unwrap();
+    assert_eq!(&*cursor.first_key().unwrap().unwrap(), &[0]);
+    assert_eq!(&*cursor.next_key().unwrap().unwrap(), &[1]);
+    assert_eq!(cursor.next_key().unwrap(), None);
+}
+
+#[test]
+fn read_only_tables() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.create_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    txn.put(t, &[0], &[1]).unwrap();
+    txn.commit().unwrap();
+
+    let mut read = db.begin_read().unwrap();
+    let tid = read.open_table("t").unwrap();
+    let t = read.get_table(tid).unwrap();
+    assert!(matches!(t.put(&[0], &[7]), Err(Error::ReadOnly)));
+    assert!(matches!(t.put(&[1], &[7]), Err(Error::ReadOnly)));
+    assert!(matches!(t.delete(&[0]), Err(Error::ReadOnly)));
+
+    // Tables from a read transaction can't be written through a write transaction either.
+    let txn = db.begin_transaction().unwrap();
+    assert!(matches!(txn.put(t, &[0], &[7]), Err(Error::ReadOnly)));
+    assert!(matches!(txn.put(t, &[1], &[7]), Err(Error::ReadOnly)));
+    assert!(matches!(txn.delete(t, &[0]), Err(Error::ReadOnly)));
+    drop(txn);
+
+    let mut cursor = read.cursor(t).unwrap();
+    cursor.first_key().unwrap();
+    assert!(matches!(cursor.delete_current(), Err(Error::ReadOnly)));
+    drop(cursor);
+
+    assert_eq!(read.get(t, &[0]).unwrap(), Some(vec![1]));
+    assert_eq!(read.get(t, &[1]).unwrap(), None);
+    drop(read);
+
+    let mut read = db.begin_read().unwrap();
+    let tid = read.open_table("t").unwrap();
+    let t = read.get_table(tid).unwrap();
+    assert_eq!(read.get(t, &[0]).unwrap(), Some(vec![1]));
+    assert_eq!(read.get(t, &[1]).unwrap(), None);
+}
+
+#[test]
+fn read_during_write() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    // A read transaction can begin while a write transaction is in progress, and doesn't observe
+    // its uncommitted changes.
+    let mut txn = db.begin_transaction().unwrap();
+    txn.create_table("t").unwrap();
+
+    let mut read = db.begin_read().unwrap();
+    assert!(read.list_tables().unwrap().is_empty());
+    assert!(read.open_table("t").is_err());
+}
+
+#[test]
+fn reader_pins_generation() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.create_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    txn.put(t, &[0], &[0]).unwrap();
+    txn.commit().unwrap();
+
+    let mut old_read = db.begin_read().unwrap();
+    assert_eq!(old_read.generation(), Some(Generation(1)));
+
+    // Commit while the old reader is active.
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.open_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    txn.put(t, &[0], &[1]).unwrap();
+    txn.commit().unwrap();
+
+    // The old reader still sees its generation, which remains on disk.
+    assert!(root_path.path().join("gen-1").exists());
+    let tid = old_read.open_table("t").unwrap();
+    let t = old_read.get_table(tid).unwrap();
+    assert_eq!(old_read.get(t, &[0]).unwrap(), Some(vec![0]));
+
+    // New readers see the latest commit.
+    let mut new_read = db.begin_read().unwrap();
+    assert_eq!(new_read.generation(), Some(Generation(2)));
+    let tid = new_read.open_table("t").unwrap();
+    let t = new_read.get_table(tid).unwrap();
+    assert_eq!(new_read.get(t, &[0]).unwrap(), Some(vec![1]));
+
+    // Dropping the last reader of the old generation deletes it.
+    drop(old_read);
+    db.wait_for_reclaim();
+    assert!(!root_path.path().join("gen-1").exists());
+    assert!(root_path.path().join("gen-2").exists());
+}
@@ -0,0 +1,52 @@
+use super::test_root;
+use crate::{Database, Generation};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn reader_follows_commits() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.create_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    txn.put(t, &[0], &[1]).unwrap();
+    txn.commit().unwrap();
+
+    let reader = Database::open_reader(root_path.path().to_path_buf()).unwrap();
+    assert_eq!(reader.generation(), Generation(1));
+
+    let mut old_read = reader.begin_read().unwrap();
+    let tid = old_read.open_table("t").unwrap();
+    let t = old_read.get_table(tid).unwrap();
+    assert_eq!(old_read.get(t, &[0]).unwrap(), Some(vec![1]));
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.open_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    txn.put(t, &[0], &[2]).unwrap();
+    txn.commit().unwrap();
+
+    // New read transactions move to the latest commit.
+    let mut new_read = reader.begin_read().unwrap();
+    assert_eq!(new_read.generation(), Some(Generation(2)));
+    let tid = new_read.open_table("t").unwrap();
+    let t = new_read.get_table(tid).unwrap();
+    assert_eq!(new_read.get(t, &[0]).unwrap(), Some(vec![2]));
+
+    // The old read transaction keeps its view, and its generation isn't deleted by the writer.
+    db.wait_for_reclaim();
+    let old_path = root_path.path().join(Generation(1).dir_name());
+    assert!(old_path.exists());
+    let t = old_read.get_table(tid).unwrap();
+    assert_eq!(old_read.get(t, &[0]).unwrap(), Some(vec![1]));
+
+    // Once released, the writer deletes the old generation on its next retry.
+    drop(old_read);
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while old_path.exists() && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(100));
+    }
+    assert!(!old_path.exists());
+}
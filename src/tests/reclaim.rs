@@ -0,0 +1,68 @@
+use super::test_root;
+use crate::{Database, Generation, RecoveryReport};
+use std::fs;
+
+#[test]
+fn aborted_transaction_reclaimed() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    txn.create_table("t").unwrap();
+    assert!(root_path.path().join("gen-1").exists());
+    drop(txn);
+
+    db.wait_for_reclaim();
+    assert!(!root_path.path().join("gen-1").exists());
+}
+
+#[test]
+fn many_commits_reclaimed() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    txn.create_table("t").unwrap();
+    txn.commit().unwrap();
+
+    for i in 0..16u8 {
+        let mut txn = db.begin_transaction().unwrap();
+        let tid = txn.open_table("t").unwrap();
+        let t = txn.get_table(tid).unwrap();
+        txn.put(t, &[i], &[i]).unwrap();
+        txn.commit().unwrap();
+    }
+
+    db.wait_for_reclaim();
+    for i in 0..17 {
+        assert!(!root_path.path().join(Generation(i).dir_name()).exists());
+    }
+    assert!(root_path.path().join(Generation(17).dir_name()).exists());
+}
+
+#[test]
+fn resume_reclaim_after_restart() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+    drop(db);
+
+    // Simulate a deletion that was still pending when the process exited.
+    let stale_path = root_path.path().join(Generation(5).dir_name());
+    fs::create_dir(&stale_path).unwrap();
+
+    let db = Database::open_or_create(root_path.path().to_path_buf()).unwrap();
+    assert_eq!(
+        *db.recovery_report(),
+        RecoveryReport::Recovered {
+            committed: Generation(0),
+            discarded: vec![Generation(5)],
+        }
+    );
+    db.wait_for_reclaim();
+    assert!(!stale_path.exists());
+
+    // New generations are numbered after the stale one.
+    let txn = db.begin_transaction().unwrap();
+    txn.commit().unwrap();
+    assert_eq!(db.begin_read().unwrap().generation(), Some(Generation(6)));
+}
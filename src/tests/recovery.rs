@@ -0,0 +1,171 @@
+use super::test_root;
+use crate::{Database, Error, Generation, RecoveryReport};
+use std::fs;
+use std::path::Path;
+
+#[test]
+fn reopen_clean() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.create_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    txn.put(t, &[0], &[1]).unwrap();
+    txn.commit().unwrap();
+    drop(db);
+
+    let db = Database::open_or_create(root_path.path().to_path_buf()).unwrap();
+    assert_eq!(*db.recovery_report(), RecoveryReport::Clean);
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.open_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    assert_eq!(txn.get(t, &[0]).unwrap(), Some(vec![1]));
+}
+
+#[test]
+fn crash_during_transaction() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    // Simulate the process dying mid-transaction by leaking the transaction, which leaves its
+    // write snapshot on disk.
+    let mut txn = db.begin_transaction().unwrap();
+    txn.create_table("t").unwrap();
+    std::mem::forget(txn);
+    drop(db);
+
+    let db = Database::open_or_create(root_path.path().to_path_buf()).unwrap();
+    assert_eq!(
+        *db.recovery_report(),
+        RecoveryReport::Recovered {
+            committed: Generation(0),
+            discarded: vec![Generation(1)],
+        }
+    );
+    db.wait_for_reclaim();
+    assert!(!root_path.path().join("gen-1").exists());
+
+    // The uncommitted table should not be visible.
+    let mut txn = db.begin_transaction().unwrap();
+    assert!(txn.open_table("t").is_err());
+}
+
+#[test]
+fn crash_after_commit_marker() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    txn.create_table("t").unwrap();
+    txn.commit().unwrap();
+    drop(db);
+
+    // Simulate a commit that wrote its marker but died before deleting the old generation.
+    fs::create_dir(root_path.path().join("gen-0")).unwrap();
+
+    let db = Database::open_or_create(root_path.path().to_path_buf()).unwrap();
+    assert_eq!(
+        *db.recovery_report(),
+        RecoveryReport::Recovered {
+            committed: Generation(1),
+            discarded: vec![Generation(0)],
+        }
+    );
+
+    let mut txn = db.begin_transaction().unwrap();
+    txn.open_table("t").unwrap();
+}
+
+#[test]
+fn multiple_generations_without_marker() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+    drop(db);
+
+    fs::remove_file(root_path.path().join("committed")).unwrap();
+    fs::create_dir(root_path.path().join("gen-1")).unwrap();
+
+    assert!(matches!(
+        Database::open_or_create(root_path.path().to_path_buf()),
+        Err(Error::UncleanShutdown)
+    ));
+}
+
+/// Create a database with one table, then move its generation to the legacy `name` directory
+/// with a legacy commit marker, if `marker` is set.
+fn legacy_database(root: &Path, name: &str, marker: bool) {
+    let db = Database::create(root.to_path_buf()).unwrap();
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.create_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    txn.put(t, &[0], &[1]).unwrap();
+    txn.commit().unwrap();
+    drop(db);
+
+    fs::rename(root.join("gen-1"), root.join(name)).unwrap();
+    if marker {
+        fs::write(root.join("committed"), name).unwrap();
+    } else {
+        fs::remove_file(root.join("committed")).unwrap();
+    }
+}
+
+fn assert_migrated(db: &Database, root: &Path) {
+    for name in ["tick", "tock"] {
+        assert!(!root.join(name).exists());
+    }
+    let mut txn = db.begin_read().unwrap();
+    let tid = txn.open_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    assert_eq!(txn.get(t, &[0]).unwrap(), Some(vec![1]));
+}
+
+#[test]
+fn migrate_legacy_generations() {
+    let root_path = test_root();
+    let root = root_path.path();
+    legacy_database(root, "tock", true);
+    // The write snapshot of an interrupted legacy transaction.
+    fs::create_dir(root.join("tick")).unwrap();
+
+    let db = Database::open_or_create(root.to_path_buf()).unwrap();
+    assert_eq!(
+        *db.recovery_report(),
+        RecoveryReport::Recovered {
+            committed: Generation(0),
+            discarded: vec![Generation(1)],
+        }
+    );
+    assert_migrated(&db, root);
+    drop(db);
+
+    let db = Database::open_or_create(root.to_path_buf()).unwrap();
+    assert_eq!(*db.recovery_report(), RecoveryReport::Clean);
+    assert_migrated(&db, root);
+}
+
+#[test]
+fn migrate_legacy_generation_without_marker() {
+    let root_path = test_root();
+    let root = root_path.path();
+    legacy_database(root, "tick", false);
+
+    let db = Database::open_or_create(root.to_path_buf()).unwrap();
+    assert_eq!(*db.recovery_report(), RecoveryReport::Clean);
+    assert_migrated(&db, root);
+}
+
+#[test]
+fn legacy_marker_without_generation() {
+    let root_path = test_root();
+    let root = root_path.path();
+    legacy_database(root, "tick", true);
+    fs::rename(root.join("tick"), root.join("elsewhere")).unwrap();
+
+    assert!(matches!(
+        Database::open_or_create(root.to_path_buf()),
+        Err(Error::LegacyMigration(_))
+    ));
+}
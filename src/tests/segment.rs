@@ -0,0 +1,85 @@
+use super::test_root;
+use crate::segment::list_segments;
+use crate::{Database, TableOptions, ValueStorage};
+
+#[test]
+fn values_are_packed() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let options = TableOptions {
+        value_storage: ValueStorage::Packed { segment_size: 64 },
+        ..TableOptions::default()
+    };
+    let tid = txn.create_table_with("t", options).unwrap();
+    let t = txn.get_table(tid).unwrap();
+    for i in 0..10u8 {
+        txn.put(t, &[i], &[i; 16]).unwrap();
+    }
+    assert!(!t.key_path(&[0]).unwrap().exists());
+    assert_eq!(list_segments(&t.path).unwrap(), vec![0, 1, 2]);
+    txn.commit().unwrap();
+
+    // A later transaction appends to the last segment rather than starting a new one.
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.open_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    txn.put(t, &[10], &[10; 16]).unwrap();
+    assert_eq!(list_segments(&t.path).unwrap(), vec![0, 1, 2]);
+
+    for i in 0..=10u8 {
+        assert_eq!(txn.get(t, &[i]).unwrap(), Some(vec![i; 16]));
+    }
+    txn.delete(t, &[0]).unwrap();
+    assert_eq!(txn.get(t, &[0]).unwrap(), None);
+}
+
+#[test]
+fn compaction() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let options = TableOptions {
+        value_storage: ValueStorage::Packed { segment_size: 64 },
+        ..TableOptions::default()
+    };
+    let tid = txn.create_table_with("t", options).unwrap();
+    let t = txn.get_table(tid).unwrap();
+    for i in 0..8u8 {
+        txn.put(t, &[i], &[i; 16]).unwrap();
+    }
+    txn.commit().unwrap();
+
+    let old_read = db.begin_read().unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.open_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    // Segment 0 holds keys 0..4: overwrite three of them, leaving it 75% garbage.
+    for i in 0..3u8 {
+        txn.put(t, &[i], &[100 + i; 16]).unwrap();
+    }
+    // Segment 1 holds keys 4..8: delete one, leaving it 25% garbage.
+    txn.delete(t, &[4]).unwrap();
+
+    assert_eq!(txn.compact_table(t, 0.5).unwrap(), 1);
+    assert!(!list_segments(&t.path).unwrap().contains(&0));
+    assert!(list_segments(&t.path).unwrap().contains(&1));
+    for i in 0..3u8 {
+        assert_eq!(txn.get(t, &[i]).unwrap(), Some(vec![100 + i; 16]));
+    }
+    assert_eq!(txn.get(t, &[3]).unwrap(), Some(vec![3; 16]));
+    assert_eq!(txn.get(t, &[4]).unwrap(), None);
+    assert_eq!(txn.compact_table(t, 0.5).unwrap(), 0);
+    txn.commit().unwrap();
+
+    // The old generation still has its own copy of the compacted segment.
+    let mut old_read = old_read;
+    let tid = old_read.open_table("t").unwrap();
+    let t = old_read.get_table(tid).unwrap();
+    for i in 0..8u8 {
+        assert_eq!(old_read.get(t, &[i]).unwrap(), Some(vec![i; 16]));
+    }
+}
@@ -0,0 +1,153 @@
+use super::test_root;
+use crate::table::{decode_table_name, encode_table_name};
+use crate::{Database, Error};
+
+fn create_tables(db: &Database, names: &[&str]) {
+    let mut txn = db.begin_transaction().unwrap();
+    for name in names {
+        let tid = txn.create_table(name).unwrap();
+        let t = txn.get_table(tid).unwrap();
+        txn.put(t, name.as_bytes(), &[1]).unwrap();
+    }
+    txn.commit().unwrap();
+}
+
+#[test]
+fn list_and_exists() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+    create_tables(&db, &["b", "a", "c"]);
+
+    let txn = db.begin_transaction().unwrap();
+    assert_eq!(txn.list_tables().unwrap(), vec!["a", "b", "c"]);
+    assert!(txn.table_exists("a"));
+    assert!(!txn.table_exists("d"));
+}
+
+#[test]
+fn drop_and_rename() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+    create_tables(&db, &["a", "b", "c"]);
+
+    let mut txn = db.begin_transaction().unwrap();
+    txn.drop_table("a").unwrap();
+    txn.rename_table("b", "d").unwrap();
+    assert_eq!(txn.list_tables().unwrap(), vec!["c", "d"]);
+
+    // Not visible to readers until commit.
+    let read = db.begin_read().unwrap();
+    assert_eq!(read.list_tables().unwrap(), vec!["a", "b", "c"]);
+    drop(read);
+
+    txn.commit().unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    assert_eq!(txn.list_tables().unwrap(), vec!["c", "d"]);
+    let tid = txn.open_table("d").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    assert_eq!(txn.get(t, b"b").unwrap(), Some(vec![1]));
+}
+
+#[test]
+fn drop_and_rename_errors() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+    create_tables(&db, &["a", "b"]);
+
+    let mut txn = db.begin_transaction().unwrap();
+    assert!(matches!(txn.drop_table("z"), Err(Error::TableNotFound(_))));
+    assert!(matches!(
+        txn.rename_table("a", "b"),
+        Err(Error::TableExists(_))
+    ));
+
+    txn.open_table("a").unwrap();
+    assert!(matches!(txn.drop_table("a"), Err(Error::TableOpen(_))));
+    assert!(matches!(
+        txn.rename_table("a", "c"),
+        Err(Error::TableOpen(_))
+    ));
+}
+
+#[test]
+fn open_missing_table() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    assert!(matches!(txn.open_table("z"), Err(Error::TableNotFound(name)) if name == "z"));
+    drop(txn);
+
+    let mut read = db.begin_read().unwrap();
+    assert!(matches!(read.open_table("z"), Err(Error::TableNotFound(name)) if name == "z"));
+}
+
+#[test]
+fn unsafe_names_are_encoded() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+    let names = [
+        "../tock",
+        "a/b",
+        "index.sqlite",
+        ".hidden",
+        "50%",
+        "ünïcödé",
+    ];
+    create_tables(&db, &names);
+
+    let mut expected = names.to_vec();
+    expected.sort();
+    let mut txn = db.begin_transaction().unwrap();
+    assert_eq!(txn.list_tables().unwrap(), expected);
+
+    // Nothing escaped the generation directory.
+    assert!(!root_path.path().join("tock").exists());
+
+    for name in names {
+        let tid = txn.open_table(name).unwrap();
+        let t = txn.get_table(tid).unwrap();
+        assert_eq!(txn.get(t, name.as_bytes()).unwrap(), Some(vec![1]));
+    }
+}
+
+#[test]
+fn invalid_and_internal_names() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+    let mut txn = db.begin_transaction().unwrap();
+
+    assert!(matches!(
+        txn.create_table(""),
+        Err(Error::InvalidTableName(_))
+    ));
+    let long_name = "/".repeat(100);
+    assert!(matches!(
+        txn.create_table(&long_name),
+        Err(Error::InvalidTableName(_))
+    ));
+
+    // Tables named after internal files get a different directory, so they can't shadow them.
+    for name in ["index.sqlite", "options.txt"] {
+        assert_eq!(decode_table_name(name), None);
+        let encoded = encode_table_name(name).unwrap();
+        assert_ne!(encoded, name);
+        assert_eq!(decode_table_name(&encoded).as_deref(), Some(name));
+        txn.create_table(name).unwrap();
+    }
+    assert_eq!(txn.list_tables().unwrap(), ["index.sqlite", "options.txt"]);
+}
+
+#[test]
+fn encoding_round_trip() {
+    for name in ["plain", "a b", "%41", "日本", "-_-"] {
+        let encoded = encode_table_name(name).unwrap();
+        assert!(!encoded.contains('.') && !encoded.contains('/'));
+        assert_eq!(decode_table_name(&encoded).as_deref(), Some(name));
+    }
+    // Internal files and non-canonical encodings are not table names.
+    assert_eq!(decode_table_name("index.sqlite"), None);
+    assert_eq!(decode_table_name("%61"), None);
+    assert_eq!(decode_table_name("%4"), None);
+}
@@ -0,0 +1,155 @@
+use super::test_root;
+use crate::key::decode_key;
+use crate::{Database, Error, Ordered, TypedTable};
+
+#[test]
+fn typed_put_get() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.create_table("names").unwrap();
+    let t = TypedTable::<u64, String>::new(txn.get_table(tid).unwrap());
+
+    t.put(&1, &"one".to_string()).unwrap();
+    t.put(&256, &"two hundred and fifty-six".to_string())
+        .unwrap();
+    assert_eq!(t.get(&1).unwrap().as_deref(), Some("one"));
+    assert_eq!(t.get(&2).unwrap(), None);
+
+    t.delete(&1).unwrap();
+    assert_eq!(t.get(&1).unwrap(), None);
+    txn.commit().unwrap();
+
+    let mut read = db.begin_read().unwrap();
+    let tid = read.open_table("names").unwrap();
+    let t = TypedTable::<u64, String>::new(read.get_table(tid).unwrap());
+    assert_eq!(
+        t.get(&256).unwrap().as_deref(),
+        Some("two hundred and fifty-six")
+    );
+
+    // Decoding with the wrong type is an error rather than a panic.
+    let wrong = TypedTable::<u32, String>::new(t.table());
+    let mut cursor = wrong.cursor().unwrap();
+    assert!(matches!(cursor.first_key(), Err(Error::Decode(_))));
+}
+
+#[test]
+fn typed_cursor_numeric_order() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.create_table("t").unwrap();
+    let t = TypedTable::<u32, Vec<u8>>::new(txn.get_table(tid).unwrap());
+
+    let keys = [1000, 1, 70000, 255, 256];
+    for k in keys {
+        t.put(&k, &k.to_le_bytes().to_vec()).unwrap();
+    }
+
+    let mut sorted = keys.to_vec();
+    sorted.sort();
+
+    let mut cursor = t.cursor().unwrap();
+    let mut seen = vec![];
+    while let Some((k, v)) = cursor.get_current().unwrap() {
+        assert_eq!(v, k.to_le_bytes());
+        seen.push(k);
+        if cursor.next_key().unwrap().is_none() {
+            break;
+        }
+    }
+    assert_eq!(seen, sorted);
+    assert_eq!(cursor.last_key().unwrap(), Some(70000));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn bincode_values() {
+    use crate::Bincode;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.create_table("points").unwrap();
+    let t = TypedTable::<String, Bincode<Point>>::new(txn.get_table(tid).unwrap());
+
+    let p = Point { x: -1, y: 2 };
+    t.put(&"p".to_string(), &Bincode(p.clone())).unwrap();
+    assert_eq!(t.get(&"p".to_string()).unwrap(), Some(Bincode(p)));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn bincode_encode_error() {
+    use crate::Bincode;
+    use serde::ser::{SerializeSeq, Serializer};
+    use serde::{Deserialize, Serialize};
+
+    /// Serialized as a sequence of unknown length, which bincode doesn't support.
+    #[derive(Debug, Deserialize)]
+    struct Unsized(Vec<u8>);
+
+    impl Serialize for Unsized {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut seq = serializer.serialize_seq(None)?;
+            for byte in &self.0 {
+                seq.serialize_element(byte)?;
+            }
+            seq.end()
+        }
+    }
+
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.create_table("t").unwrap();
+    let t = TypedTable::<Bincode<Unsized>, Vec<u8>>::new(txn.get_table(tid).unwrap());
+
+    let key = Bincode(Unsized(vec![1, 2]));
+    assert!(matches!(t.put(&key, &vec![0]), Err(Error::Encode(_))));
+    assert!(matches!(t.get(&key), Err(Error::Encode(_))));
+    assert!(matches!(t.delete(&key), Err(Error::Encode(_))));
+}
+
+#[test]
+fn ordered_composite_keys() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let tid = txn.create_table("roots").unwrap();
+    let t = TypedTable::<Ordered<(u64, [u8; 32])>, u8>::new(txn.get_table(tid).unwrap());
+
+    let keys = [
+        (256, [0; 32]),
+        (1, [0xff; 32]),
+        (1, [0; 32]),
+        (255, [7; 32]),
+    ];
+    for (i, key) in keys.iter().enumerate() {
+        t.put(&Ordered(*key), &(i as u8)).unwrap();
+    }
+
+    let mut sorted = keys.to_vec();
+    sorted.sort();
+
+    // Read back through the untyped cursor and decode the raw keys.
+    let mut cursor = txn.cursor(t.table()).unwrap();
+    let mut seen = vec![];
+    while let Some(key) = cursor.next_key().unwrap() {
+        seen.push(decode_key::<(u64, [u8; 32])>(&key).unwrap());
+    }
+    assert_eq!(seen, sorted);
+}
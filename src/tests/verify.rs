@@ -0,0 +1,147 @@
+use super::test_root;
+use crate::{
+    ChecksumMode, Comparator, Database, Generation, Inconsistency, TableOptions, ValueStorage,
+};
+use std::fs;
+
+#[test]
+fn clean_database() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    for (name, value_storage, fan_out) in [
+        ("file", ValueStorage::File, 2),
+        ("inline", ValueStorage::Inline { threshold: 4 }, 0),
+        ("packed", ValueStorage::Packed { segment_size: 64 }, 0),
+    ] {
+        let options = TableOptions {
+            value_storage,
+            fan_out,
+            checksum: ChecksumMode::Crc32c,
+            ..TableOptions::default()
+        };
+        let tid = txn.create_table_with(name, options).unwrap();
+        let t = txn.get_table(tid).unwrap();
+        for i in 0..10u8 {
+            txn.put(t, &[i], &vec![i; i as usize]).unwrap();
+        }
+    }
+    txn.commit().unwrap();
+
+    assert!(db.verify().unwrap().is_clean());
+}
+
+#[test]
+fn detect_and_repair() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let mut txn = db.begin_transaction().unwrap();
+    let options = TableOptions {
+        checksum: ChecksumMode::Crc32c,
+        ..TableOptions::default()
+    };
+    let tid = txn.create_table_with("t", options).unwrap();
+    let t = txn.get_table(tid).unwrap();
+    for key in [b"a", b"b", b"c"] {
+        txn.put(t, key, b"value").unwrap();
+    }
+    txn.commit().unwrap();
+
+    // Damage the committed generation.
+    let mut txn = db.begin_read().unwrap();
+    let tid = txn.open_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    fs::remove_file(t.key_path(b"a").unwrap()).unwrap();
+    fs::write(t.key_path(b"b").unwrap(), b"vandal").unwrap();
+    fs::write(t.key_path(b"z").unwrap(), b"orphan").unwrap();
+    fs::write(t.path.join("not-hex"), b"junk").unwrap();
+    fs::write(t.path.join("foo.bak"), b"junk").unwrap();
+    let table_path = t.path.clone();
+    drop(txn);
+
+    let report = db.verify().unwrap();
+    assert_eq!(
+        report.inconsistencies,
+        vec![
+            Inconsistency::MissingValue {
+                table: "t".to_string(),
+                key: b"a".to_vec(),
+            },
+            Inconsistency::CorruptValue {
+                table: "t".to_string(),
+                key: b"b".to_vec(),
+            },
+            Inconsistency::OrphanValueFile {
+                table: "t".to_string(),
+                path: table_path.join("7a"),
+                key: Some(b"z".to_vec()),
+            },
+            Inconsistency::UndecodableFileName {
+                table: "t".to_string(),
+                path: table_path.join("foo.bak"),
+            },
+            Inconsistency::UndecodableFileName {
+                table: "t".to_string(),
+                path: table_path.join("not-hex"),
+            },
+        ]
+    );
+
+    // Repair happens in a new generation, so the paths differ.
+    let repaired = db.verify_and_repair().unwrap();
+    assert_eq!(repaired.inconsistencies.len(), 5);
+    assert!(db.verify().unwrap().is_clean());
+
+    let mut txn = db.begin_read().unwrap();
+    let tid = txn.open_table("t").unwrap();
+    let t = txn.get_table(tid).unwrap();
+    assert_eq!(txn.get(t, b"a").unwrap(), None);
+    assert_eq!(txn.get(t, b"b").unwrap(), None);
+    assert_eq!(txn.get(t, b"c").unwrap(), Some(b"value".to_vec()));
+    assert!(!t.path.join("not-hex").exists());
+    assert!(!t.path.join("foo.bak").exists());
+}
+
+#[test]
+fn stray_generation() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let stray_path = root_path.path().join(Generation(100).dir_name());
+    fs::create_dir(&stray_path).unwrap();
+    assert_eq!(
+        db.verify().unwrap().inconsistencies,
+        vec![Inconsistency::StrayGeneration(Generation(100))]
+    );
+
+    // Repair leaves stray generations for the next open to remove.
+    db.verify_and_repair().unwrap();
+    assert!(stray_path.exists());
+    fs::remove_dir(&stray_path).unwrap();
+    assert!(db.verify().unwrap().is_clean());
+}
+
+#[test]
+fn custom_comparator_tables_are_skipped() {
+    let root_path = test_root();
+    let db = Database::create(root_path.path().to_path_buf()).unwrap();
+
+    let reversed = Comparator {
+        name: "reversed",
+        compare: |a, b| b.cmp(a),
+    };
+    let mut txn = db.begin_transaction().unwrap();
+    txn.create_table_with_comparator("t", TableOptions::default(), &reversed)
+        .unwrap();
+    txn.commit().unwrap();
+
+    assert_eq!(
+        db.verify().unwrap().inconsistencies,
+        vec![Inconsistency::SkippedTable {
+            table: "t".to_string(),
+            comparator: "reversed".to_string(),
+        }]
+    );
+}
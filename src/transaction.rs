@@ -1,89 +1,166 @@
-use crate::{Cursor, Error, IndexFile, Snapshot, Table, TableId};
-use parking_lot::{MutexGuard, RwLock};
-use std::fs::{self, File};
-use std::io::{self, Read, Write};
-use std::path::{Path, PathBuf};
+use crate::table::{check_comparator, list_tables, table_dir_path};
+use crate::{
+    Comparator, Cursor, Database, Error, Generation, IndexFile, KeyOrdering, Snapshot, Table,
+    TableId, TableOptions,
+};
+use parking_lot::MutexGuard;
+use std::fs;
+use std::path::PathBuf;
 
 #[derive(Debug)]
 pub struct Transaction<'a> {
-    pub(crate) read_snapshot: &'a RwLock<Snapshot>,
-    pub(crate) _txn_lock: MutexGuard<'a, ()>,
+    pub(crate) db: &'a Database,
+    pub(crate) _txn_lock: MutexGuard<'a, Generation>,
     pub(crate) write_snapshot: Snapshot,
     pub(crate) open_tables: Vec<Table>,
-    pub(crate) committed: bool,
-}
-
-impl<'a> Drop for Transaction<'a> {
-    fn drop(&mut self) {
-        if !self.committed {
-            // Try to delete the aborted write snapshot.
-            // FIXME(sproul): remove unwrap
-            fs::remove_dir_all(&self.write_snapshot.path).unwrap();
-            assert!(!self.write_snapshot.path.exists());
-        }
-    }
 }
 
 impl<'a> Transaction<'a> {
-    pub fn commit(mut self) -> Result<(), Error> {
-        // Obtain a write lock on the read snapshot, ensuring there are no readers active.
-        let mut read_snapshot = self.read_snapshot.write();
-
-        // Update the read snapshot with the results of the current transaction.
-        std::mem::swap(&mut *read_snapshot, &mut self.write_snapshot);
-
-        // Delete the previous read snapshot from disk (now `self.write_snapshot`).
-        // FIXME(sproul): this is probably slow, could delete in the background.
-        fs::remove_dir_all(&self.write_snapshot.path)?;
-        assert!(!self.write_snapshot.path.exists());
-
-        self.committed = true;
-
-        // Drop write lock on `read_snapshot`, allowing new readers to observe the changes.
-        drop(read_snapshot);
-
-        Ok(())
+    /// Commit the transaction, making its changes visible to new readers.
+    ///
+    /// If the transaction is dropped without being committed its write snapshot is deleted.
+    pub fn commit(self) -> Result<(), Error> {
+        self.db.commit_snapshot(self.write_snapshot)
     }
 
     /// Path to the directory for a table.
-    ///
-    /// Assume table names are filesystem safe.
-    fn table_path(&self, name: &str) -> PathBuf {
-        self.write_snapshot.path.join(name)
+    fn table_path(&self, name: &str) -> Result<PathBuf, Error> {
+        table_dir_path(&self.write_snapshot.path, name)
     }
 
-    /// Path to the index file for a table.
-    fn index_file_path(table_path: &Path) -> PathBuf {
-        table_path.join("index.sqlite")
+    /// Path to the directory for a table, which must exist.
+    fn existing_table_path(&self, name: &str) -> Result<PathBuf, Error> {
+        let path = self.table_path(name)?;
+        if !path.is_dir() {
+            return Err(Error::TableNotFound(name.to_string()));
+        }
+        Ok(path)
     }
 
-    /// Create a table in the database with `name`.
+    /// Create a table in the database with `name` and the default options.
     ///
     /// Return the ID of the table.
     pub fn create_table(&mut self, name: &str) -> Result<TableId, Error> {
-        let path = self.table_path(name);
+        self.create_table_with(name, TableOptions::default())
+    }
+
+    /// Create a table in the database with `name` and `options`.
+    ///
+    /// The options are stored with the table and apply whenever it is opened.
+    pub fn create_table_with(
+        &mut self,
+        name: &str,
+        options: TableOptions,
+    ) -> Result<TableId, Error> {
+        self.create_table_inner(name, options, None)
+    }
+
+    /// Create a table in the database with `name` and `options`, whose keys are ordered by
+    /// `comparator`.
+    ///
+    /// The table must be opened with the same comparator from then on.
+    pub fn create_table_with_comparator(
+        &mut self,
+        name: &str,
+        mut options: TableOptions,
+        comparator: &Comparator,
+    ) -> Result<TableId, Error> {
+        Comparator::validate_name(comparator.name)?;
+        options.key_ordering = KeyOrdering::Custom(comparator.name.to_string());
+        self.create_table_inner(name, options, Some(comparator))
+    }
+
+    fn create_table_inner(
+        &mut self,
+        name: &str,
+        options: TableOptions,
+        comparator: Option<&Comparator>,
+    ) -> Result<TableId, Error> {
+        check_comparator(&options, comparator)?;
+        options.compression.check_supported()?;
+        let path = self.table_path(name)?;
         fs::create_dir(&path)?;
+        options.compression.apply_to_dir(&path)?;
 
-        let index_file = IndexFile::create(Self::index_file_path(&path))?;
+        options.write(&path)?;
+        let index_file =
+            IndexFile::create_with_comparator(Table::index_file_path(&path), comparator)?;
 
         let id = TableId::new(self.open_tables.len());
-        self.open_tables.push(Table { path, index_file });
+        self.open_tables.push(Table::new(path, index_file, options));
 
         Ok(id)
     }
 
     // TODO(sproul): consider using interior mutabilty to enable returning a `Table`.
     pub fn open_table(&mut self, name: &str) -> Result<TableId, Error> {
-        let path = self.table_path(name);
-
-        if path.is_dir() {
-            let id = TableId::new(self.open_tables.len());
-            let index_file = IndexFile::open(Self::index_file_path(&path))?;
-            self.open_tables.push(Table { path, index_file });
-            Ok(id)
-        } else {
-            Err(Error::Oops)
+        let table = Table::open(self.existing_table_path(name)?)?;
+        let id = TableId::new(self.open_tables.len());
+        self.open_tables.push(table);
+        Ok(id)
+    }
+
+    /// Open a table created by `create_table_with_comparator`.
+    pub fn open_table_with_comparator(
+        &mut self,
+        name: &str,
+        comparator: &Comparator,
+    ) -> Result<TableId, Error> {
+        let table = Table::open_with_comparator(self.table_path(name)?, Some(comparator), false)?;
+        let id = TableId::new(self.open_tables.len());
+        self.open_tables.push(table);
+        Ok(id)
+    }
+
+    /// List the names of all tables in the write snapshot, in sorted order.
+    pub fn list_tables(&self) -> Result<Vec<String>, Error> {
+        list_tables(&self.write_snapshot.path)
+    }
+
+    /// Check whether a table called `name` exists in the write snapshot.
+    pub fn table_exists(&self, name: &str) -> bool {
+        self.table_path(name).is_ok_and(|path| path.is_dir())
+    }
+
+    /// Delete the table called `name` and all of its keys.
+    ///
+    /// The table must not have been opened or created by this transaction.
+    pub fn drop_table(&mut self, name: &str) -> Result<(), Error> {
+        let path = self.existing_closed_table_path(name)?;
+        fs::remove_dir_all(path)?;
+        Ok(())
+    }
+
+    /// Rename the table called `old` to `new`.
+    ///
+    /// The table must not have been opened or created by this transaction.
+    pub fn rename_table(&mut self, old: &str, new: &str) -> Result<(), Error> {
+        let old_path = self.existing_closed_table_path(old)?;
+        let new_path = self.table_path(new)?;
+        if new_path.exists() {
+            return Err(Error::TableExists(new.to_string()));
+        }
+        fs::rename(old_path, new_path)?;
+        Ok(())
+    }
+
+    /// Move the value files of the table called `name` into the fan-out layout with `fan_out`
+    /// levels of directories.
+    ///
+    /// The table must not have been opened or created by this transaction.
+    pub fn migrate_fan_out(&mut self, name: &str, fan_out: u8) -> Result<(), Error> {
+        let path = self.existing_closed_table_path(name)?;
+        let mut table = Table::open(path)?;
+        table.migrate_fan_out(fan_out)
+    }
+
+    /// Path to the table called `name`, checking that it exists and isn't in `open_tables`.
+    fn existing_closed_table_path(&self, name: &str) -> Result<PathBuf, Error> {
+        let path = self.existing_table_path(name)?;
+        if self.open_tables.iter().any(|table| table.path == path) {
+            return Err(Error::TableOpen(name.to_string()));
         }
+        Ok(path)
     }
 
     pub fn get_table(&self, id: TableId) -> Result<&Table, Error> {
@@ -91,35 +168,32 @@ impl<'a> Transaction<'a> {
     }
 
     pub fn put(&self, table: &Table, key: &[u8], value: &[u8]) -> Result<(), Error> {
-        let key_path = table.key_path(key);
-        let mut key_file = File::create(&key_path)?;
-        key_file.write_all(value)?;
-        table.index_file.put_key(key)?;
-        Ok(())
+        table.put(key, value)
     }
 
     pub fn get(&self, table: &Table, key: &[u8]) -> Result<Option<Vec<u8>>, Error> {
-        let key_path = table.key_path(key);
-        let mut key_file = match File::open(key_path) {
-            Ok(f) => f,
-            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
-            Err(e) => return Err(e.into()),
-        };
-        let mut bytes = vec![];
-        key_file.read_to_end(&mut bytes)?;
-        Ok(Some(bytes))
+        table.get(key)
+    }
+
+    /// Read all values for `key`, in sorted order.
+    pub fn get_all(&self, table: &Table, key: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+        table.get_all(key)
     }
 
     pub fn delete(&self, table: &Table, key: &[u8]) -> Result<(), Error> {
-        let key_path = table.key_path(key);
-        fs::remove_file(key_path).or_else(|e| {
-            if e.kind() == io::ErrorKind::NotFound {
-                Ok(())
-            } else {
-                Err(e)
-            }
-        })?;
-        table.index_file.delete_key(key)
+        table.delete(key)
+    }
+
+    /// Compact the segment files of a table with packed value storage.
+    ///
+    /// See `Table::compact`.
+    pub fn compact_table(&self, table: &Table, max_garbage_ratio: f64) -> Result<usize, Error> {
+        table.compact(max_garbage_ratio)
+    }
+
+    /// Remove one `value` of `key`, leaving its other values in place.
+    pub fn delete_dup(&self, table: &Table, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        table.delete_dup(key, value)
     }
 
     pub fn cursor<'b>(&'b self, table: &'a Table) -> Result<Cursor<'b>, Error> {
@@ -0,0 +1,215 @@
+//! Typed wrappers around tables, converting keys and values to and from bytes with codecs.
+use crate::{Cursor, Error, Table};
+use std::borrow::Cow;
+use std::marker::PhantomData;
+
+/// Conversion between a key type and the bytes stored in the index.
+///
+/// Keys are ordered by their encoded bytes, so the encoding determines iteration order.
+pub trait KeyCodec: Sized {
+    fn encode_key(&self) -> Result<Cow<'_, [u8]>, Error>;
+
+    fn decode_key(bytes: &[u8]) -> Result<Self, Error>;
+}
+
+/// Conversion between a value type and the bytes stored on disk.
+pub trait ValueCodec: Sized {
+    fn encode_value(&self) -> Result<Cow<'_, [u8]>, Error>;
+
+    fn decode_value(bytes: &[u8]) -> Result<Self, Error>;
+}
+
+impl KeyCodec for Vec<u8> {
+    fn encode_key(&self) -> Result<Cow<'_, [u8]>, Error> {
+        Ok(Cow::Borrowed(self))
+    }
+
+    fn decode_key(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(bytes.to_vec())
+    }
+}
+
+impl ValueCodec for Vec<u8> {
+    fn encode_value(&self) -> Result<Cow<'_, [u8]>, Error> {
+        Ok(Cow::Borrowed(self))
+    }
+
+    fn decode_value(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(bytes.to_vec())
+    }
+}
+
+impl KeyCodec for String {
+    fn encode_key(&self) -> Result<Cow<'_, [u8]>, Error> {
+        Ok(Cow::Borrowed(self.as_bytes()))
+    }
+
+    fn decode_key(bytes: &[u8]) -> Result<Self, Error> {
+        String::from_utf8(bytes.to_vec()).map_err(|e| Error::Decode(e.to_string()))
+    }
+}
+
+impl ValueCodec for String {
+    fn encode_value(&self) -> Result<Cow<'_, [u8]>, Error> {
+        self.encode_key()
+    }
+
+    fn decode_value(bytes: &[u8]) -> Result<Self, Error> {
+        Self::decode_key(bytes)
+    }
+}
+
+/// Unsigned integers are encoded big-endian, so their byte order matches their numeric order.
+macro_rules! impl_big_endian_codec {
+    ($($int:ty),*) => {
+        $(
+            impl KeyCodec for $int {
+                fn encode_key(&self) -> Result<Cow<'_, [u8]>, Error> {
+                    Ok(Cow::Owned(self.to_be_bytes().to_vec()))
+                }
+
+                fn decode_key(bytes: &[u8]) -> Result<Self, Error> {
+                    let bytes = bytes.try_into().map_err(|_| {
+                        Error::Decode(format!(
+                            "expected {} bytes for {}, got {}",
+                            std::mem::size_of::<$int>(),
+                            stringify!($int),
+                            bytes.len()
+                        ))
+                    })?;
+                    Ok(<$int>::from_be_bytes(bytes))
+                }
+            }
+
+            impl ValueCodec for $int {
+                fn encode_value(&self) -> Result<Cow<'_, [u8]>, Error> {
+                    self.encode_key()
+                }
+
+                fn decode_value(bytes: &[u8]) -> Result<Self, Error> {
+                    Self::decode_key(bytes)
+                }
+            }
+        )*
+    };
+}
+
+impl_big_endian_codec!(u8, u16, u32, u64, u128);
+
+/// Codec for any serde type, using bincode.
+///
+/// Bincode doesn't preserve ordering in general, so as a key this is only suitable for tables
+/// that are accessed by exact key rather than iterated in order.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bincode<T>(pub T);
+
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize + serde::de::DeserializeOwned> KeyCodec for Bincode<T> {
+    fn encode_key(&self) -> Result<Cow<'_, [u8]>, Error> {
+        bincode::serialize(&self.0)
+            .map(Cow::Owned)
+            .map_err(|e| Error::Encode(e.to_string()))
+    }
+
+    fn decode_key(bytes: &[u8]) -> Result<Self, Error> {
+        bincode::deserialize(bytes)
+            .map(Bincode)
+            .map_err(|e| Error::Decode(e.to_string()))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize + serde::de::DeserializeOwned> ValueCodec for Bincode<T> {
+    fn encode_value(&self) -> Result<Cow<'_, [u8]>, Error> {
+        self.encode_key()
+    }
+
+    fn decode_value(bytes: &[u8]) -> Result<Self, Error> {
+        Self::decode_key(bytes)
+    }
+}
+
+/// A table whose keys and values are typed.
+#[derive(Debug)]
+pub struct TypedTable<'t, K, V> {
+    table: &'t Table,
+    _phantom: PhantomData<fn() -> (K, V)>,
+}
+
+impl<'t, K: KeyCodec, V: ValueCodec> TypedTable<'t, K, V> {
+    pub fn new(table: &'t Table) -> Self {
+        Self {
+            table,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// The untyped table underneath.
+    pub fn table(&self) -> &'t Table {
+        self.table
+    }
+
+    pub fn put(&self, key: &K, value: &V) -> Result<(), Error> {
+        self.table.put(&key.encode_key()?, &value.encode_value()?)
+    }
+
+    pub fn get(&self, key: &K) -> Result<Option<V>, Error> {
+        self.table
+            .get(&key.encode_key()?)?
+            .map(|bytes| V::decode_value(&bytes))
+            .transpose()
+    }
+
+    pub fn delete(&self, key: &K) -> Result<(), Error> {
+        self.table.delete(&key.encode_key()?)
+    }
+
+    pub fn cursor(&self) -> Result<TypedCursor<'t, K, V>, Error> {
+        Ok(TypedCursor {
+            cursor: Cursor::new(self.table)?,
+            _phantom: PhantomData,
+        })
+    }
+}
+
+/// A cursor over a `TypedTable`, decoding keys and values as it goes.
+#[derive(Debug)]
+pub struct TypedCursor<'t, K, V> {
+    cursor: Cursor<'t>,
+    _phantom: PhantomData<fn() -> (K, V)>,
+}
+
+impl<'t, K: KeyCodec, V: ValueCodec> TypedCursor<'t, K, V> {
+    pub fn first_key(&mut self) -> Result<Option<K>, Error> {
+        self.cursor
+            .first_key()?
+            .map(|key| K::decode_key(&key))
+            .transpose()
+    }
+
+    pub fn last_key(&mut self) -> Result<Option<K>, Error> {
+        self.cursor
+            .last_key()?
+            .map(|key| K::decode_key(&key))
+            .transpose()
+    }
+
+    pub fn next_key(&mut self) -> Result<Option<K>, Error> {
+        self.cursor
+            .next_key()?
+            .map(|key| K::decode_key(&key))
+            .transpose()
+    }
+
+    pub fn get_current(&mut self) -> Result<Option<(K, V)>, Error> {
+        let Some((key, value)) = self.cursor.get_current()? else {
+            return Ok(None);
+        };
+        Ok(Some((K::decode_key(&key)?, V::decode_value(&value)?)))
+    }
+
+    pub fn delete_current(&mut self) -> Result<(), Error> {
+        self.cursor.delete_current()
+    }
+}
@@ -0,0 +1,113 @@
+//! Offline consistency checking of tables and generations.
+//!
+//! Verification compares each table's index against the value files in its directory and checks
+//! that every value can be read back. It's meant to be run while no other transactions are open,
+//! because generations pinned by readers are indistinguishable from stray ones.
+use crate::options::OPTIONS_FILENAME;
+use crate::segment::parse_segment_name;
+use crate::table::{FAN_OUT_STAGING_DIRNAME, INDEX_FILENAME};
+use crate::{Error, Generation, TableId};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A problem found by verification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inconsistency {
+    /// The key is in the index but its value is missing.
+    MissingValue { table: String, key: Vec<u8> },
+    /// The value of the key doesn't match its checksum, is truncated, or can't be decoded.
+    CorruptValue { table: String, key: Vec<u8> },
+    /// A value file that doesn't belong to any key in the index.
+    ///
+    /// `key` is the key named by the file, or `None` for tables with hashed file names.
+    OrphanValueFile {
+        table: String,
+        path: PathBuf,
+        key: Option<Vec<u8>>,
+    },
+    /// A file whose name isn't a valid name for a value file.
+    UndecodableFileName { table: String, path: PathBuf },
+    /// The table uses a custom comparator, so it can only be verified by opening it with
+    /// `open_table_with_comparator` and calling `Table::verify`.
+    SkippedTable { table: String, comparator: String },
+    /// A generation other than the committed one.
+    ///
+    /// These are removed the next time the database is opened.
+    StrayGeneration(Generation),
+}
+
+/// Result of verifying a database.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct VerifyReport {
+    pub inconsistencies: Vec<Inconsistency>,
+}
+
+impl VerifyReport {
+    pub fn is_clean(&self) -> bool {
+        self.inconsistencies.is_empty()
+    }
+}
+
+/// Pass through the result of opening table `name` for verification, or record it as skipped
+/// and return `None` if it needs a comparator.
+pub(crate) fn open_or_skip(
+    result: Result<TableId, Error>,
+    name: &str,
+    report: &mut VerifyReport,
+) -> Result<Option<TableId>, Error> {
+    match result {
+        Ok(id) => Ok(Some(id)),
+        Err(Error::ComparatorMismatch {
+            stored: Some(comparator),
+            ..
+        }) => {
+            report.inconsistencies.push(Inconsistency::SkippedTable {
+                table: name.to_string(),
+                comparator,
+            });
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// List the paths of all files in the table at `table_path` that could be value files.
+///
+/// The table's own files at the top level are skipped. Anything else is listed, so that stray
+/// files show up as undecodable or orphaned.
+pub fn list_value_files(table_path: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut paths = vec![];
+    for entry in fs::read_dir(table_path)? {
+        let entry = entry?;
+        if entry.file_name().to_str().is_some_and(is_internal_name) {
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            list_files_recursive(&entry.path(), &mut paths)?;
+        } else {
+            paths.push(entry.path());
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Whether `name` is one of the files a table keeps at the top level of its directory.
+fn is_internal_name(name: &str) -> bool {
+    name == INDEX_FILENAME
+        || name == OPTIONS_FILENAME
+        || name == FAN_OUT_STAGING_DIRNAME
+        || parse_segment_name(name).is_some()
+}
+
+fn list_files_recursive(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<(), Error> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            list_files_recursive(&entry.path(), paths)?;
+        } else {
+            paths.push(entry.path());
+        }
+    }
+    Ok(())
+}
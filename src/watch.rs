@@ -0,0 +1,80 @@
+//! Notification of commits made by another process, using inotify.
+use crate::recovery::COMMIT_MARKER_FILENAME;
+use crate::Error;
+use std::ffi::{CStr, CString};
+use std::io;
+use std::mem::size_of;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+/// Watches a database root for the commit marker being replaced.
+#[derive(Debug)]
+pub struct CommitWatcher {
+    fd: OwnedFd,
+}
+
+impl CommitWatcher {
+    pub fn new(root_path: &Path) -> Result<Self, Error> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        // The commit marker is written to a temporary file and renamed into place.
+        let path = CString::new(root_path.as_os_str().as_bytes()).map_err(|_| Error::Oops)?;
+        let wd =
+            unsafe { libc::inotify_add_watch(fd.as_raw_fd(), path.as_ptr(), libc::IN_MOVED_TO) };
+        if wd < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(Self { fd })
+    }
+
+    /// Return whether the commit marker has changed since the last call, without blocking.
+    pub fn poll(&self) -> Result<bool, Error> {
+        // Aligned buffer for `inotify_event` structs.
+        let mut buf = [0u64; 512];
+        let mut changed = false;
+        loop {
+            let len = unsafe {
+                libc::read(
+                    self.fd.as_raw_fd(),
+                    buf.as_mut_ptr().cast(),
+                    size_of::<[u64; 512]>(),
+                )
+            };
+            if len < 0 {
+                let e = io::Error::last_os_error();
+                if e.kind() == io::ErrorKind::WouldBlock {
+                    return Ok(changed);
+                }
+                return Err(e.into());
+            }
+
+            let bytes =
+                unsafe { std::slice::from_raw_parts(buf.as_ptr().cast::<u8>(), len as usize) };
+            let mut offset = 0;
+            while offset < bytes.len() {
+                let event = unsafe {
+                    bytes
+                        .as_ptr()
+                        .add(offset)
+                        .cast::<libc::inotify_event>()
+                        .read_unaligned()
+                };
+                let name_start = offset + size_of::<libc::inotify_event>();
+                let name = &bytes[name_start..name_start + event.len as usize];
+                offset = name_start + event.len as usize;
+
+                // If events were dropped, assume we missed a commit.
+                if event.mask & libc::IN_Q_OVERFLOW != 0 {
+                    changed = true;
+                } else if let Ok(name) = CStr::from_bytes_until_nul(name) {
+                    changed |= name.to_bytes() == COMMIT_MARKER_FILENAME.as_bytes();
+                }
+            }
+        }
+    }
+}